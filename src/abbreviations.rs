@@ -0,0 +1,138 @@
+use crate::error::Fb2SqliteError;
+use crate::migel::normalize_for_match;
+use std::error::Error;
+
+/// `--abbreviations`: full forms of abbreviations common in product names
+/// ("i.v.=intravenös", "kath.=katheter"). They are written out in product
+/// text and in MiGeL texts before keywords are extracted, so both sides
+/// compare the full words.
+#[derive(Clone, Default)]
+pub struct Abbreviations {
+    /// Normalized abbreviation -> normalized full form, longest first
+    entries: Vec<(String, String)>,
+}
+
+impl Abbreviations {
+    /// Read a file of `abbreviation=full form` lines; empty lines and `#`
+    /// comments are skipped. Both sides are normalized like product text and
+    /// an abbreviation may be given only once.
+    pub fn load(path: &str) -> Result<Abbreviations, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let mut abbreviations = Abbreviations::default();
+        for (line_nr, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse_error = |message: String| {
+                Fb2SqliteError::Parse(format!("{}:{}: {}", path, line_nr + 1, message))
+            };
+            let Some((short, full)) = line.split_once('=') else {
+                return Err(parse_error("expected abbreviation=full form".into()).into());
+            };
+            let short = normalize_for_match(short.trim());
+            let full = normalize_for_match(full.trim());
+            if short.is_empty() || full.is_empty() {
+                return Err(parse_error("missing the abbreviation or its full form".into()).into());
+            }
+            if abbreviations.entries.iter().any(|(s, _)| *s == short) {
+                let message = format!("abbreviation \"{}\" is given twice", short);
+                return Err(parse_error(message).into());
+            }
+            abbreviations.entries.push((short, full));
+        }
+        // Longest first, so "i.v.inj." wins over "i.v."
+        abbreviations
+            .entries
+            .sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        Ok(abbreviations)
+    }
+
+    /// `text` normalized, with every abbreviation that stands as a word of
+    /// its own replaced by its full form ("kath." in "ballon-kath. 12f", not
+    /// in "ekath."). Lines are kept, so secondary keywords still see them.
+    pub fn expand(&self, text: &str) -> String {
+        let normalized = normalize_for_match(text);
+        if self.entries.is_empty() {
+            return normalized;
+        }
+        let mut expanded = String::with_capacity(normalized.len());
+        let mut rest = normalized.as_str();
+        let mut after_word = false;
+        while let Some(c) = rest.chars().next() {
+            let found = self.entries.iter().find(|(short, _)| {
+                !after_word
+                    && rest.starts_with(short.as_str())
+                    && !(short.ends_with(char::is_alphanumeric)
+                        && rest[short.len()..].starts_with(char::is_alphanumeric))
+            });
+            if let Some((short, full)) = found {
+                expanded.push_str(full);
+                rest = &rest[short.len()..];
+                // "kath.12f" becomes "katheter 12f", not one word "katheter12f"
+                let joined = rest.starts_with(char::is_alphanumeric);
+                if joined && full.ends_with(char::is_alphanumeric) {
+                    expanded.push(' ');
+                }
+                after_word = false;
+                continue;
+            }
+            expanded.push(c);
+            rest = &rest[c.len_utf8()..];
+            after_word = c.is_alphanumeric();
+        }
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Abbreviations loaded from a temporary file with `content`.
+    fn abbreviations(name: &str, content: &str) -> Result<Abbreviations, Box<dyn Error>> {
+        let file = format!(
+            "fb2sqlite-abbreviations-{}-{}.txt",
+            name,
+            std::process::id()
+        );
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, content)?;
+        let abbreviations = Abbreviations::load(&path.to_string_lossy());
+        std::fs::remove_file(&path)?;
+        abbreviations
+    }
+
+    #[test]
+    fn expand_writes_out_abbreviations_that_are_words() {
+        let abbreviations =
+            abbreviations("words", "# catheters\nkath.=Katheter\ni.v.=intravenös\n").unwrap();
+        assert_eq!(
+            abbreviations.expand("Ballon-Kath. 12F"),
+            "ballon-katheter 12f"
+        );
+        assert_eq!(abbreviations.expand("Kanüle i.v."), "kanuele intravenoes");
+        // Inside a word or joined to the next one
+        assert_eq!(abbreviations.expand("ekath."), "ekath.");
+        assert_eq!(abbreviations.expand("kath.12f"), "katheter 12f");
+    }
+
+    #[test]
+    fn expand_prefers_the_longest_abbreviation() {
+        let abbreviations =
+            abbreviations("longest", "i.v.=intravenös\ni.v.inj.=Injektion\n").unwrap();
+        assert_eq!(abbreviations.expand("i.v.inj. 5ml"), "injektion 5ml");
+    }
+
+    #[test]
+    fn expand_without_abbreviations_only_normalizes() {
+        assert_eq!(Abbreviations::default().expand("Kath. GRÜN"), "kath. gruen");
+    }
+
+    #[test]
+    fn load_rejects_duplicates_and_missing_full_forms() {
+        assert!(abbreviations("duplicate", "kath.=Katheter\nKATH.=Kanüle\n").is_err());
+        assert!(abbreviations("empty", "kath.=\n").is_err());
+        assert!(abbreviations("separator", "kath. Katheter\n").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::error::Fb2SqliteError;
+use crate::migel::{normalize_for_match, COMPOUND_MIN_LEN};
+use std::collections::BTreeSet;
+use std::error::Error;
+
+/// `--blocklist`: words of products outside the MiGeL scope ("schokolade",
+/// "shampoo"). A product whose text contains one of them is left unmatched
+/// whatever its score, so it cannot get a best-effort MiGeL code.
+#[derive(Default)]
+pub struct Blocklist {
+    /// Normalized blocked words
+    words: BTreeSet<String>,
+}
+
+impl Blocklist {
+    /// Read a file of blocked words, one per line; empty lines and `#`
+    /// comments are skipped. The words are normalized like product text.
+    pub fn load(path: &str) -> Result<Blocklist, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let mut blocklist = Blocklist::default();
+        for (line_nr, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let word = normalize_for_match(line);
+            if !word.chars().all(char::is_alphanumeric) {
+                return Err(Fb2SqliteError::Parse(format!(
+                    "{}:{}: blocked word \"{}\" is not a single word",
+                    path,
+                    line_nr + 1,
+                    line
+                ))
+                .into());
+            }
+            blocklist.words.insert(word);
+        }
+        Ok(blocklist)
+    }
+
+    /// Whether one of the product texts contains a blocked word. A word
+    /// ending in a blocked word of `COMPOUND_MIN_LEN` or more chars counts as
+    /// well ("milchschokolade" for "schokolade").
+    pub fn blocks(&self, texts: &[&str]) -> bool {
+        texts.iter().any(|text| {
+            normalize_for_match(text)
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .any(|w| {
+                    self.words.contains(w)
+                        || self
+                            .words
+                            .iter()
+                            .any(|b| b.len() >= COMPOUND_MIN_LEN && w.ends_with(b.as_str()))
+                })
+        })
+    }
+}
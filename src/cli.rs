@@ -0,0 +1,839 @@
+use crate::abbreviations::Abbreviations;
+use crate::compare::{parse_variant, MatcherVariant};
+use crate::computed::ComputedKind;
+use crate::date;
+use crate::db::DEFAULT_TABLE;
+use crate::download::USER_AGENT;
+use crate::migel::{Language, MatchOptions, TieBreak};
+use crate::output;
+use crate::renames::{is_identifier, ColumnRenames};
+use crate::report::ReportFormat;
+use crate::synonyms::Synonyms;
+use crate::upload::{UploadOptions, REMOTE_DEST};
+use chrono::NaiveDate;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use reqwest::header::{HeaderName, HeaderValue};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+const SUBCOMMANDS: &[&str] = &[
+    "import", "migel", "match", "upload", "explain", "tune", "doctor", "help",
+];
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CsvEncoding {
+    Utf8,
+    Latin1,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    Csv,
+    Json,
+}
+
+/// File format of the `import`/`migel` output.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// SQLite database with a `data` table
+    Sqlite,
+    /// Parquet file with the same columns, for analytics tools
+    Parquet,
+}
+
+#[derive(Parser)]
+#[command(name = "fb2sqlite")]
+pub struct Cli {
+    /// Directory for firstbase.csv, migel.xlsx and the output database (created if missing)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub work_dir: Option<PathBuf>,
+
+    /// Abort the run after this many seconds (exit code 124), e.g. a stuck download under cron
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub max_runtime: Option<u64>,
+
+    #[command(flatten)]
+    pub http: HttpArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Request settings shared by the feed and MiGeL downloads.
+#[derive(clap::Args)]
+pub struct HttpArgs {
+    /// User-Agent header sent with every download
+    #[arg(long, global = true, value_name = "STRING", default_value = USER_AGENT)]
+    pub user_agent: String,
+
+    /// Extra request header for every download, e.g. "Authorization: Bearer TOKEN" (repeatable)
+    #[arg(
+        long = "header",
+        global = true,
+        value_name = "NAME: VALUE",
+        value_parser = parse_header
+    )]
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// Parse a `--header` value of the form `Name: Value`.
+fn parse_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected NAME: VALUE, got {}", s))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("invalid header name \"{}\"", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("invalid value for header {}", name))?;
+    Ok((name, value))
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Download the GS1 CSV, convert it to firstbase.db and upload it (default)
+    Import(ImportArgs),
+    /// Map products to MiGeL codes and write only the matched products
+    Migel(Box<MigelArgs>),
+    /// Print the best MiGeL candidates for a single product description
+    Match(MatchCommandArgs),
+    /// Upload an existing database file
+    Upload(UploadCommandArgs),
+    /// Show why the product with this GTIN gets its MiGeL code, with the matched keywords marked
+    Explain(ExplainArgs),
+    /// Sweep the match score threshold over labeled products and print precision, recall and F1
+    Tune(TuneArgs),
+    /// Check the prerequisites of a run (scp/ssh, feed and MiGeL URLs, work directory, SQLite)
+    Doctor(DoctorArgs),
+}
+
+/// Where the GS1 CSV comes from and how it is decoded.
+#[derive(clap::Args)]
+pub struct InputArgs {
+    /// Use local firstbase.csv instead of downloading (useful when GS1 server is slow)
+    #[arg(long)]
+    pub local_csv: bool,
+
+    /// Read the products from this CSV file instead of the GS1 feed
+    #[arg(long, value_name = "PATH", conflicts_with = "local_csv")]
+    pub csv_file: Option<String>,
+
+    /// Product feed URL instead of the GS1 default; repeat to merge several feeds (adds a
+    /// `source` column)
+    #[arg(long, value_name = "URL", conflicts_with = "csv_file")]
+    pub source_url: Vec<String>,
+
+    /// Abort when the downloaded (or cached) feed is smaller than this many bytes (0 disables)
+    #[arg(long, value_name = "BYTES", default_value_t = 10_000)]
+    pub min_csv_bytes: u64,
+
+    /// Character encoding of the CSV feed (a UTF-8 BOM always selects UTF-8)
+    #[arg(long, value_enum, default_value_t = CsvEncoding::Utf8)]
+    pub encoding: CsvEncoding,
+
+    /// Format of the product feed; `json` requests JSON from GS1 (cached as firstbase.json)
+    #[arg(long, value_enum, default_value_t = InputFormat::Csv)]
+    pub input_format: InputFormat,
+
+    /// Skip (and count) malformed CSV records instead of aborting the run
+    #[arg(long)]
+    pub skip_bad_rows: bool,
+}
+
+impl InputArgs {
+    /// Several `--source-url` feeds are merged into one table.
+    pub fn merged(&self) -> bool {
+        self.source_url.len() > 1
+    }
+}
+
+/// SCP upload settings.
+#[derive(clap::Args)]
+pub struct UploadArgs {
+    /// SCP destination: `HOST:DIR/` keeps the local file name, `HOST:PATH` sets the remote name
+    #[arg(long, value_name = "HOST:PATH", default_value = REMOTE_DEST)]
+    pub remote_dest: String,
+
+    /// ~/.ssh/config host alias replacing the host of --remote-dest (user, port, key come from it)
+    #[arg(long, value_name = "NAME", value_parser = parse_host_alias)]
+    pub ssh_host_alias: Option<String>,
+
+    /// Retry a failed upload this many times (default: single attempt)
+    #[arg(long, default_value_t = 0)]
+    pub upload_retries: u32,
+
+    /// After uploading, compare the remote sha256sum (via ssh) with the local file
+    #[arg(long)]
+    pub verify_upload: bool,
+
+    /// SSH identity file for the upload (passed to scp/ssh as -i)
+    #[arg(long, value_name = "PATH")]
+    pub ssh_key: Option<String>,
+
+    /// SSH port for the upload (scp -P / ssh -p)
+    #[arg(long, value_name = "N")]
+    pub ssh_port: Option<u16>,
+
+    /// Suppress upload progress (scp -q, no heartbeat messages)
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Capture scp's output and log it (stderr in the error on failure) instead of the terminal
+    #[arg(long)]
+    pub quiet_scp: bool,
+
+    /// Before uploading, diff the data table schema (--table-name) with the deployed database
+    #[arg(long)]
+    pub check_remote_schema: bool,
+
+    /// With --check-remote-schema: upload even if the schema changed
+    #[arg(long, requires = "check_remote_schema")]
+    pub allow_schema_change: bool,
+
+    /// Open a new SSH connection for every scp/ssh call instead of sharing one
+    #[arg(long)]
+    pub no_ssh_multiplex: bool,
+
+    /// Upload under a date-stamped remote name (`firstbase_<dd.mm.yyyy>.db`), keeping older uploads
+    #[arg(long)]
+    pub remote_versioned: bool,
+
+    /// With --remote-versioned: make the plain remote name a symlink to the new upload (via ssh)
+    #[arg(long, requires = "remote_versioned")]
+    pub update_latest_link: bool,
+}
+
+impl UploadArgs {
+    pub fn options(&self) -> UploadOptions {
+        UploadOptions {
+            retries: self.upload_retries,
+            verify: self.verify_upload,
+            ssh_key: self.ssh_key.clone(),
+            ssh_port: self.ssh_port,
+            quiet: self.quiet,
+            capture_scp: self.quiet_scp,
+            check_schema: self.check_remote_schema,
+            allow_schema_change: self.allow_schema_change,
+            multiplex: !self.no_ssh_multiplex,
+            versioned: self.remote_versioned,
+            update_latest: self.update_latest_link,
+            table: DEFAULT_TABLE.to_string(),
+        }
+    }
+
+    /// `--remote-dest` with its host replaced by `--ssh-host-alias`, which
+    /// scp and ssh resolve through the ssh config.
+    pub fn destination(&self) -> String {
+        match (&self.ssh_host_alias, self.remote_dest.split_once(':')) {
+            (Some(alias), Some((_, path))) => format!("{}:{}", alias, path),
+            _ => self.remote_dest.clone(),
+        }
+    }
+
+    /// `options` for deploying the database of `import`/`migel`: the schema
+    /// check compares its `--table-name`.
+    pub fn deploy_options(&self, db: &DbArgs) -> UploadOptions {
+        UploadOptions {
+            table: db.table_name.clone(),
+            ..self.options()
+        }
+    }
+}
+
+/// Output database settings.
+#[derive(clap::Args)]
+pub struct DbArgs {
+    /// Derived columns appended to every row, e.g. `desc-normalized`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub computed_columns: Vec<ComputedKind>,
+
+    /// Add a gtin14 column: the first column zero-padded to 14 digits if it is a valid GTIN
+    #[arg(long)]
+    pub normalize_gtin: bool,
+
+    /// Add a product_keywords column: the description keywords as used for MiGeL matching
+    #[arg(long)]
+    pub product_keywords: bool,
+
+    /// Add pharmacode_clean, gtin_clean and identifier_flags (malformed codes kept and flagged)
+    #[arg(long)]
+    pub clean_identifiers: bool,
+
+    /// Write only these CSV columns (header names or zero-based indexes), in the given order
+    #[arg(long, value_name = "NAME|INDEX,...", value_delimiter = ',')]
+    pub columns: Vec<String>,
+
+    /// File of `original=new_name` lines renaming output columns (others keep their sanitized name)
+    #[arg(long, value_name = "PATH")]
+    pub rename_columns: Option<String>,
+
+    /// Drop columns that are empty in every written row from the table
+    #[arg(long)]
+    pub prune_empty_columns: bool,
+
+    /// VACUUM the database after writing to shrink the uploaded file (can be slow)
+    #[arg(long)]
+    pub vacuum: bool,
+
+    /// Run ANALYZE after writing so SQLite's query planner has index statistics
+    #[arg(long)]
+    pub analyze: bool,
+
+    /// Add an FTS5 table <table>_fts over the description columns (and migel_bezeichnung)
+    #[arg(long)]
+    pub fts: bool,
+
+    /// Name of the output table (letters, digits and _), e.g. to keep several feeds in one database
+    #[arg(
+        long,
+        value_name = "NAME",
+        default_value = DEFAULT_TABLE,
+        value_parser = parse_table_name
+    )]
+    pub table_name: String,
+
+    /// SQLite page cache of the database writer in MiB (default: SQLite's 2 MiB)
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u32).range(1..))]
+    pub sqlite_cache_mb: Option<u32>,
+
+    /// Memory-map up to MB MiB of the database in the writer (default: off)
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u32).range(1..))]
+    pub sqlite_mmap_mb: Option<u32>,
+
+    /// Write the database to PATH instead of the default name; `-` streams it to stdout
+    /// (logs go to stderr, nothing is uploaded)
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<String>,
+
+    /// Build the database as <output>.tmp and rename it to the output name only once it is complete
+    #[arg(long)]
+    pub temp_db: bool,
+
+    /// Output file format; `parquet` writes firstbase.parquet (or firstbase_migel_<date>.parquet)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Sqlite)]
+    pub output_format: OutputFormat,
+}
+
+impl DbArgs {
+    /// `--output -`: the database is streamed to stdout.
+    pub fn to_stdout(&self) -> bool {
+        self.output.as_deref() == Some("-")
+    }
+
+    /// The database file to write: `--output`, a temporary file when it is
+    /// streamed to stdout, otherwise `default` (with a `.parquet` extension
+    /// for `--output-format parquet`).
+    pub fn output_path(&self, default: String) -> String {
+        match self.output.as_deref() {
+            Some("-") => output::temp_db_path(),
+            Some(path) => path.to_string(),
+            None if self.output_format == OutputFormat::Parquet => Path::new(&default)
+                .with_extension("parquet")
+                .to_string_lossy()
+                .into_owned(),
+            None => default,
+        }
+    }
+
+    /// `--rename-columns`, loaded (empty without the flag).
+    pub fn column_renames(&self) -> Result<ColumnRenames, Box<dyn Error>> {
+        match &self.rename_columns {
+            Some(path) => ColumnRenames::load(path),
+            None => Ok(ColumnRenames::default()),
+        }
+    }
+
+    /// Computed columns to append; `--normalize-gtin` adds `gtin14` and
+    /// `--product-keywords` adds `product_keywords`, `--clean-identifiers`
+    /// adds `pharmacode_clean`, `gtin_clean` and `identifier_flags`.
+    pub fn computed_kinds(&self) -> Vec<ComputedKind> {
+        let mut kinds = self.computed_columns.clone();
+        let shorthands = [
+            (self.normalize_gtin, ComputedKind::Gtin14),
+            (self.product_keywords, ComputedKind::ProductKeywords),
+            (self.clean_identifiers, ComputedKind::PharmacodeClean),
+            (self.clean_identifiers, ComputedKind::GtinClean),
+            (self.clean_identifiers, ComputedKind::IdentifierFlags),
+        ];
+        for (enabled, kind) in shorthands {
+            if enabled && !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
+}
+
+/// MiGeL parsing and matching settings.
+#[derive(clap::Args)]
+pub struct MatchArgs {
+    /// Minimum number of distinct keyword hits before a MiGeL item is eligible
+    #[arg(long, default_value_t = 1)]
+    pub min_matches: usize,
+
+    /// Order of candidates with equal scores (the position number always breaks remaining ties)
+    #[arg(long, value_enum, default_value_t = TieBreak::Length)]
+    pub tie_break: TieBreak,
+
+    /// Leave products unmatched whose description yields fewer than N keyword tokens
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub min_product_tokens: usize,
+
+    /// Weight (0 to 1) of MiGeL category keywords relative to the item's own DE keywords
+    #[arg(long, value_name = "F", default_value_t = 0.0, value_parser = parse_weight)]
+    pub category_weight: f64,
+
+    /// Minimum score (0 to 1) of a multi-keyword match; single-keyword matches need 0.5 or more
+    #[arg(long, value_name = "F", default_value_t = 0.3, value_parser = parse_weight)]
+    pub min_score: f64,
+
+    /// Let only keywords of 6+ chars match inside German compounds ("band" no longer in "verband")
+    #[arg(long)]
+    pub word_boundary: bool,
+
+    /// Also match German keywords by Kölner Phonetik code ("Kateter" for "Katheter"), half weight
+    #[arg(long)]
+    pub phonetic: bool,
+
+    /// File of comma-separated synonym groups, e.g. `rollstuhl, fahrstuhl`, one per line
+    #[arg(long, value_name = "PATH")]
+    pub synonyms: Option<String>,
+
+    /// File of `abbreviation=full form` lines, e.g. `kath.=katheter`, expanded in both texts
+    #[arg(long, value_name = "PATH")]
+    pub abbreviations: Option<String>,
+
+    /// MiGeL sheet languages used for keywords, e.g. `de,fr` (the German sheet is always read)
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "de,fr,it")]
+    pub sheet_languages: Vec<Language>,
+
+    /// File of `position_nr: keyword, keyword` lines merged into the MiGeL item keywords
+    #[arg(long, value_name = "PATH")]
+    pub migel_keywords: Option<String>,
+
+    /// Local MiGeL workbook `[EDITION=]PATH` (no download); repeat per edition, oldest first
+    #[arg(long, value_name = "[EDITION=]PATH", value_parser = parse_migel_file)]
+    pub migel_file: Vec<MigelFile>,
+
+    /// Read the MiGeL items from a CSV (position_nr, bezeichnung, limitation[, keywords, betrag])
+    #[arg(long, value_name = "PATH", conflicts_with = "migel_file")]
+    pub migel_csv: Option<String>,
+
+    /// Testing aid: read ready-made MiGeL items from a JSON array (no download, no workbook)
+    #[arg(
+        long,
+        value_name = "JSON",
+        hide = true,
+        conflicts_with_all = ["migel_file", "migel_csv"]
+    )]
+    pub seed_migel_items: Option<String>,
+
+    /// Score against keywords from all lines of the MiGeL Bezeichnung, not just the first
+    #[arg(long)]
+    pub full_description_keywords: bool,
+
+    /// Match on the MiGeL items' own Bezeichnung only, without their category descriptions
+    #[arg(long, conflicts_with = "category_weight")]
+    pub no_category_keywords: bool,
+
+    /// Keep only the N most distinctive primary keywords per MiGeL item and language
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_keywords_per_item: Option<u16>,
+
+    /// Write the keyword index (keyword -> MiGeL position numbers) as JSON (`.json`) or CSV
+    #[arg(long, value_name = "PATH")]
+    pub export_keywords: Option<String>,
+}
+
+/// A `--migel-file` workbook and the edition label its items are tagged with.
+#[derive(Clone)]
+pub struct MigelFile {
+    pub edition: String,
+    pub path: String,
+}
+
+/// `[EDITION=]PATH`; without a label the edition is the file name without
+/// its extension.
+fn parse_migel_file(s: &str) -> Result<MigelFile, String> {
+    let (edition, path) = match s.split_once('=') {
+        Some((edition, path)) if !edition.trim().is_empty() => (edition.trim().to_string(), path),
+        Some(_) => return Err(format!("empty edition label in {}", s)),
+        None => {
+            let stem = Path::new(s).file_stem().map(|stem| stem.to_string_lossy());
+            (stem.unwrap_or_default().into_owned(), s)
+        }
+    };
+    if path.is_empty() {
+        return Err(format!("expected [EDITION=]PATH, got {}", s));
+    }
+    Ok(MigelFile {
+        edition,
+        path: path.to_string(),
+    })
+}
+
+/// A `--ssh-host-alias`: a `Host` name of the ssh config. It is passed to
+/// scp and ssh as an argument, so it must not look like an option or carry a
+/// user or path.
+fn parse_host_alias(s: &str) -> Result<String, String> {
+    let valid = s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || ['.', '-', '_'].contains(&c));
+    if s.is_empty() || s.starts_with('-') || !valid {
+        return Err(format!("expected a host name of the ssh config, got {}", s));
+    }
+    Ok(s.to_string())
+}
+
+/// A `--table-name`: a plain SQL identifier outside SQLite's reserved
+/// `sqlite_` prefix and not the `--multi-match` or `--audit` table.
+fn parse_table_name(s: &str) -> Result<String, String> {
+    if !is_identifier(s) {
+        return Err(format!("expected letters, digits and _, got {}", s));
+    }
+    let lower = s.to_ascii_lowercase();
+    if lower.starts_with("sqlite_") || ["product_migel", "match_audit"].contains(&lower.as_str()) {
+        return Err(format!("{} is reserved", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Parse a weight between 0 and 1.
+fn parse_weight(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(w) if (0.0..=1.0).contains(&w) => Ok(w),
+        _ => Err(format!("expected a number between 0 and 1, got {}", s)),
+    }
+}
+
+impl MatchArgs {
+    /// The matching options; reads the `--synonyms` and `--abbreviations` files.
+    pub fn options(&self) -> Result<MatchOptions, Box<dyn Error>> {
+        Ok(MatchOptions {
+            min_matches: self.min_matches,
+            tie_break: self.tie_break,
+            min_product_tokens: self.min_product_tokens,
+            category_weight: self.category_weight,
+            min_score: self.min_score,
+            word_boundary: self.word_boundary,
+            phonetic: self.phonetic,
+            synonyms: match &self.synonyms {
+                Some(path) => Synonyms::load(path)?,
+                None => Synonyms::default(),
+            },
+            abbreviations: match &self.abbreviations {
+                Some(path) => Abbreviations::load(path)?,
+                None => Abbreviations::default(),
+            },
+        })
+    }
+}
+
+/// CSV columns holding the product text to match.
+#[derive(clap::Args, Default)]
+pub struct ColumnArgs {
+    /// Match this column (zero-based index or header name) instead of the GS1 DE/FR/IT descriptions
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub product_desc_column: Option<String>,
+
+    /// Columns (indexes or header names) of the DE, FR and IT description and the brand used
+    /// for matching (default: the GS1 columns 5,6,7,8)
+    #[arg(
+        long,
+        value_name = "DE,FR,IT[,BRAND]",
+        value_delimiter = ',',
+        conflicts_with = "product_desc_column"
+    )]
+    pub match_columns: Vec<String>,
+}
+
+/// Delta builds: only rows with a recent last-modified date.
+#[derive(clap::Args)]
+pub struct SinceArgs {
+    /// Only process rows whose --date-column is on or after DATE (e.g. 2026-10-14 or 14.10.2026)
+    #[arg(long, value_name = "DATE", value_parser = parse_since, requires = "date_column")]
+    pub since: Option<NaiveDate>,
+
+    /// Column (zero-based index or header name) with the last-modified date, for --since
+    #[arg(long, value_name = "INDEX|NAME", requires = "since")]
+    pub date_column: Option<String>,
+
+    /// With --since: process rows with an empty or unparseable date instead of skipping them
+    #[arg(long, requires = "since")]
+    pub include_undated: bool,
+}
+
+/// Parse the `--since` cutoff with the rules used for the date column.
+fn parse_since(s: &str) -> Result<NaiveDate, String> {
+    date::parse_date(s)
+        .ok_or_else(|| format!("expected a date like 2026-10-14 or 14.10.2026, got {}", s))
+}
+
+#[derive(clap::Args)]
+pub struct ImportArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// The CSV has no header row: name the columns col1, col2, ... and import the first row too
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// Exit without building or uploading when the feed equals the one of the last run
+    #[arg(long)]
+    pub skip_unchanged: bool,
+
+    #[command(flatten)]
+    pub since: SinceArgs,
+
+    #[command(flatten)]
+    pub db: DbArgs,
+
+    #[command(flatten)]
+    pub upload: UploadArgs,
+
+    /// Also append the rows to a persistent archive database (table `data` with a run_date column)
+    #[arg(long, value_name = "DB")]
+    pub archive: Option<String>,
+
+    /// End-of-run summary on stdout: `text`, `json` (logs then go to stderr) or `none`
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub report_format: ReportFormat,
+}
+
+#[derive(clap::Args)]
+pub struct MigelArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub matching: MatchArgs,
+
+    #[command(flatten)]
+    pub since: SinceArgs,
+
+    #[command(flatten)]
+    pub db: DbArgs,
+
+    #[command(flatten)]
+    pub upload: UploadArgs,
+
+    /// Deploy: SCP the database to the remote server (uses plain filename without date)
+    #[arg(long)]
+    pub deploy: bool,
+
+    /// Fail before uploading if fewer than this share (0 to 1) of the data rows got a MiGeL code
+    #[arg(long, value_name = "F", value_parser = parse_weight)]
+    pub min_match_rate: Option<f64>,
+
+    /// Print the number of matched products per MiGeL category (first position segment)
+    #[arg(long)]
+    pub coverage_report: bool,
+
+    /// Print the number of matches per score bucket (0.0-0.1 ... 0.9-1.0)
+    #[arg(long)]
+    pub score_histogram: bool,
+
+    /// Print the number of matched products and their average score per MiGeL item, most first
+    #[arg(long)]
+    pub match_distribution: bool,
+
+    /// Also write a CSV of every product with its MiGeL code, Bezeichnung and score for review
+    #[arg(long, value_name = "PATH")]
+    pub list_matches_csv: Option<String>,
+
+    /// Print N randomly chosen matched rows (description, MiGeL item, score) for review
+    #[arg(long, value_name = "N")]
+    pub sample: Option<usize>,
+
+    /// Seed of the --sample selection (same seed and input give the same rows)
+    #[arg(long, value_name = "SEED", default_value_t = 0, requires = "sample")]
+    pub sample_seed: u64,
+
+    /// Add a migel_matched_keywords column listing the keywords that drove each match
+    #[arg(long)]
+    pub explain: bool,
+
+    /// CSV of reviewed `gtin,position_nr,decision` pairs; rejected MiGeL items are never assigned
+    #[arg(long, value_name = "PATH")]
+    pub overrides: Option<String>,
+
+    /// File of words (one per line) of out-of-scope products; such products stay unmatched
+    #[arg(long, value_name = "PATH")]
+    pub blocklist: Option<String>,
+
+    /// Ask on the terminal about matches scoring below --review-below and append the decisions
+    /// to --overrides
+    #[arg(long, requires = "overrides")]
+    pub review: bool,
+
+    /// With --review: matches scoring below F are reviewed
+    #[arg(
+        long,
+        value_name = "F",
+        default_value_t = 0.5,
+        value_parser = parse_weight,
+        requires = "review"
+    )]
+    pub review_below: f64,
+
+    /// Cut migel_limitation to at most N characters, ending in an ellipsis
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_limitation_length: Option<u16>,
+
+    #[command(flatten)]
+    pub columns: ColumnArgs,
+
+    /// Number of matcher threads (default: one per CPU core); SQLite writes stay single-threaded
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// Also write all matches close to the best one into a product_migel table
+    #[arg(long)]
+    pub multi_match: bool,
+
+    /// Write every matching decision (also non-matches) with score and keywords to match_audit
+    #[arg(long)]
+    pub audit: bool,
+
+    /// With --multi-match: keep matches scoring at most this much below the best
+    #[arg(long, default_value_t = 0.1, requires = "multi_match")]
+    pub match_band: f64,
+
+    /// With --multi-match: maximum number of matches per product
+    #[arg(long, default_value_t = 3, requires = "multi_match")]
+    pub max_matches: usize,
+
+    /// Also append the rows to a persistent archive database (table `data` with a run_date column)
+    #[arg(long, value_name = "DB")]
+    pub archive: Option<String>,
+
+    /// End-of-run summary on stdout: `text`, `json` (logs then go to stderr) or `none`
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub report_format: ReportFormat,
+
+    /// Download the MiGeL XLSX, print its sheets with size and header row, then exit
+    #[arg(long)]
+    pub list_sheets: bool,
+
+    /// Only load and parse the MiGeL items, print their counts and warnings, then exit
+    #[arg(long, conflicts_with = "list_sheets")]
+    pub parse_only: bool,
+
+    /// Match the products with each variant (e.g. `default,phonetic`), report agreement, then exit
+    #[arg(
+        long,
+        value_name = "VARIANT,...",
+        value_delimiter = ',',
+        value_parser = parse_variant,
+        conflicts_with_all = ["list_sheets", "parse_only"]
+    )]
+    pub compare_matchers: Vec<MatcherVariant>,
+}
+
+#[derive(clap::Args)]
+pub struct MatchCommandArgs {
+    /// Product description to match (scored as DE, FR and IT text)
+    pub description: String,
+
+    /// Number of candidates to print
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+
+    #[command(flatten)]
+    pub matching: MatchArgs,
+}
+
+#[derive(clap::Args)]
+pub struct ExplainArgs {
+    /// GTIN of the product in the first column (leading zeros are ignored)
+    pub gtin: String,
+
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub columns: ColumnArgs,
+
+    #[command(flatten)]
+    pub matching: MatchArgs,
+}
+
+#[derive(clap::Args)]
+pub struct TuneArgs {
+    /// CSV of `gtin,position_nr` labels with a header row (empty position_nr: must stay unmatched)
+    #[arg(long, value_name = "PATH")]
+    pub labels: String,
+
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub columns: ColumnArgs,
+
+    #[command(flatten)]
+    pub matching: MatchArgs,
+}
+
+#[derive(clap::Args)]
+pub struct DoctorArgs {
+    /// Product feed URL to check instead of the GS1 default; repeatable
+    #[arg(long, value_name = "URL")]
+    pub source_url: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct UploadCommandArgs {
+    /// Database files to upload, over one SSH connection
+    #[arg(required = true)]
+    pub files: Vec<String>,
+
+    #[command(flatten)]
+    pub upload: UploadArgs,
+}
+
+/// Map the pre-subcommand invocations onto subcommands, so existing cron
+/// jobs keep working: no subcommand means `import`, and the deprecated
+/// `--migel` flag selects `migel`.
+pub fn rewrite_legacy_args(mut argv: Vec<String>) -> Vec<String> {
+    // Global options (with a value) may precede the subcommand
+    let global_options: Vec<String> = Cli::command()
+        .get_arguments()
+        .filter(|arg| arg.is_global_set() && arg.get_action().takes_values())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect();
+    let mut first = 1;
+    while let Some(arg) = argv.get(first) {
+        if global_options.contains(arg) {
+            first += 2;
+        } else if global_options
+            .iter()
+            .any(|option| arg.starts_with(&format!("{}=", option)))
+        {
+            first += 1;
+        } else {
+            break;
+        }
+    }
+    let explicit = argv.get(first).is_some_and(|a| {
+        SUBCOMMANDS.contains(&a.as_str())
+            || matches!(a.as_str(), "-h" | "--help" | "-V" | "--version")
+    });
+    if explicit {
+        return argv;
+    }
+    if let Some(pos) = argv.iter().position(|a| a == "--migel") {
+        eprintln!("Warning: --migel is deprecated, use `fb2sqlite migel [OPTIONS]`");
+        argv.remove(pos);
+        argv.insert(first.min(argv.len()), "migel".to_string());
+    } else {
+        // The old default mode always uploaded, --deploy had no effect there
+        argv.retain(|a| a != "--deploy");
+        argv.insert(first.min(argv.len()), "import".to_string());
+    }
+    argv
+}
@@ -0,0 +1,123 @@
+use crate::db::{column_names, DbWriterHandle, REAL_COLUMNS};
+use crate::error::Fb2SqliteError;
+use crate::interrupt;
+use crate::number::parse_swiss_number;
+use crate::renames::ColumnRenames;
+use arrow_array::builder::{Float64Builder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::error::Error;
+use std::fs::{self, File};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Rows buffered per record batch before they are handed to the writer.
+const BATCH_ROWS: usize = 8192;
+
+/// Values of one column of the current batch.
+enum ColumnBuilder {
+    Text(StringBuilder),
+    Real(Float64Builder),
+}
+
+/// `--output-format parquet`: the counterpart of `db::spawn_db_writer`. The
+/// first row received is the header; the columns get the names of the
+/// `data` table (including `--rename-columns`), `REAL_COLUMNS` are nullable
+/// Float64 and all others Utf8. A failed or interrupted write removes the
+/// incomplete file.
+pub fn spawn_parquet_writer(
+    path: String,
+    rx: mpsc::Receiver<Vec<String>>,
+    renames: ColumnRenames,
+) -> DbWriterHandle {
+    thread::spawn(move || {
+        let result = write_parquet(&path, rx, &renames);
+        if result.is_err() {
+            // Without its footer the file is unreadable anyway
+            let _ = fs::remove_file(&path);
+        }
+        result
+    })
+}
+
+fn write_parquet(
+    path: &str,
+    rx: mpsc::Receiver<Vec<String>>,
+    renames: &ColumnRenames,
+) -> Result<Option<usize>, Box<dyn Error + Send + Sync>> {
+    let Ok(headers) = rx.recv() else {
+        return Ok(None);
+    };
+    let real_cols: Vec<bool> = headers
+        .iter()
+        .map(|h| REAL_COLUMNS.contains(&h.as_str()))
+        .collect();
+    let fields: Vec<Field> = column_names(&headers, renames, true)
+        .into_iter()
+        .zip(&real_cols)
+        .map(|(name, &real)| {
+            let data_type = if real { DataType::Float64 } else { DataType::Utf8 };
+            Field::new(name, data_type, real)
+        })
+        .collect();
+    let schema: SchemaRef = Arc::new(Schema::new(fields));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(properties))?;
+
+    let mut builders: Vec<ColumnBuilder> = real_cols
+        .iter()
+        .map(|&real| {
+            if real {
+                ColumnBuilder::Real(Float64Builder::new())
+            } else {
+                ColumnBuilder::Text(StringBuilder::new())
+            }
+        })
+        .collect();
+    let mut count = 0;
+    let mut pending = 0;
+    while let Ok(row) = rx.recv() {
+        for (i, builder) in builders.iter_mut().enumerate() {
+            let value = row.get(i).map_or("", String::as_str);
+            match builder {
+                ColumnBuilder::Text(b) => b.append_value(value),
+                // Empty (e.g. no amount) becomes null, as in SQLite
+                ColumnBuilder::Real(b) => b.append_option(parse_swiss_number(value)),
+            }
+        }
+        count += 1;
+        pending += 1;
+        if pending == BATCH_ROWS {
+            writer.write(&finish_batch(&schema, &mut builders)?)?;
+            pending = 0;
+        }
+    }
+    if pending > 0 {
+        writer.write(&finish_batch(&schema, &mut builders)?)?;
+    }
+    if interrupt::interrupted() {
+        return Err(Fb2SqliteError::Interrupted.into());
+    }
+    writer.close()?;
+    Ok(Some(count))
+}
+
+/// Turn the buffered values into a record batch and reset the builders.
+fn finish_batch(
+    schema: &SchemaRef,
+    builders: &mut [ColumnBuilder],
+) -> Result<RecordBatch, ArrowError> {
+    let columns: Vec<ArrayRef> = builders
+        .iter_mut()
+        .map(|builder| match builder {
+            ColumnBuilder::Text(b) => Arc::new(b.finish()) as ArrayRef,
+            ColumnBuilder::Real(b) => Arc::new(b.finish()) as ArrayRef,
+        })
+        .collect();
+    RecordBatch::try_new(schema.clone(), columns)
+}
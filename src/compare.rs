@@ -0,0 +1,199 @@
+use crate::migel::{MatchOptions, MigelItem, TieBreak};
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+/// Number of disagreeing products `compare_matchers` prints.
+const SAMPLE_SIZE: usize = 10;
+
+/// A change a `--compare-matchers` variant makes to the matching options.
+#[derive(Clone)]
+enum Setting {
+    Phonetic,
+    WordBoundary,
+    CategoryWeight(f64),
+    MinScore(f64),
+    MinMatches(usize),
+    TieBreak(TieBreak),
+}
+
+/// A matcher of `--compare-matchers`: the run's matching options with some
+/// settings changed, e.g. `phonetic+category-weight=0.3`.
+#[derive(Clone)]
+pub struct MatcherVariant {
+    /// The variant as given, used in the report
+    label: String,
+    settings: Vec<Setting>,
+}
+
+impl MatcherVariant {
+    /// `base` with the settings of this variant applied.
+    pub fn options(&self, base: &MatchOptions) -> MatchOptions {
+        let mut options = base.clone();
+        for setting in &self.settings {
+            match *setting {
+                Setting::Phonetic => options.phonetic = true,
+                Setting::WordBoundary => options.word_boundary = true,
+                Setting::CategoryWeight(weight) => options.category_weight = weight,
+                Setting::MinScore(score) => options.min_score = score,
+                Setting::MinMatches(count) => options.min_matches = count,
+                Setting::TieBreak(tie_break) => options.tie_break = tie_break,
+            }
+        }
+        options
+    }
+}
+
+/// Parse a `--compare-matchers` variant: `default` (the options as given)
+/// or `+`-separated settings `phonetic`, `word-boundary`,
+/// `category-weight=F`, `min-score=F`, `min-matches=N` and `tie-break=NAME`.
+pub fn parse_variant(s: &str) -> Result<MatcherVariant, String> {
+    let label = s.trim().to_string();
+    let mut settings = Vec::new();
+    if label != "default" {
+        for part in label.split('+') {
+            let fraction = |value: &str| match value.parse::<f64>() {
+                Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+                _ => Err(format!("{}: expected a number between 0 and 1", part)),
+            };
+            settings.push(match part.split_once('=') {
+                None if part == "phonetic" => Setting::Phonetic,
+                None if part == "word-boundary" => Setting::WordBoundary,
+                Some(("category-weight", value)) => Setting::CategoryWeight(fraction(value)?),
+                Some(("min-score", value)) => Setting::MinScore(fraction(value)?),
+                Some(("min-matches", value)) => match value.parse::<usize>() {
+                    Ok(count) if count > 0 => Setting::MinMatches(count),
+                    _ => return Err(format!("{}: expected a positive number", part)),
+                },
+                Some(("tie-break", value)) => Setting::TieBreak(
+                    TieBreak::from_str(value, true).map_err(|e| format!("{}: {}", part, e))?,
+                ),
+                _ => {
+                    return Err(format!(
+                        "unknown matcher setting \"{}\" (expected default, phonetic, \
+                         word-boundary, category-weight=F, min-score=F, min-matches=N or \
+                         tie-break=NAME)",
+                        part
+                    ))
+                }
+            });
+        }
+    }
+    Ok(MatcherVariant { label, settings })
+}
+
+/// The MiGeL position a matcher chose, `None` when it left the product unmatched.
+fn chosen<'a>(choice: &Option<(&'a MigelItem, f64)>) -> Option<&'a str> {
+    choice.map(|(item, _)| item.position_nr.as_str())
+}
+
+/// A product row as `compare_matchers` sees it: the whole CSV row, its
+/// first column and its description on one line.
+pub struct Product {
+    pub gtin: String,
+    pub description: String,
+    pub row: Vec<String>,
+}
+
+/// `--compare-matchers`: match every product with each variant and print
+/// the match count per variant, how often all of them (and each pair)
+/// agree, i.e. chose the same MiGeL item or all left the product
+/// unmatched, and the first `SAMPLE_SIZE` products they disagree on.
+/// `choose` matches a product with the variant of the given index, the way
+/// a `migel` run would.
+pub fn compare_matchers<'a>(
+    products: &[Product],
+    variants: &[MatcherVariant],
+    choose: impl Fn(usize, &Product) -> Option<(&'a MigelItem, f64)> + Sync,
+) {
+    let choices: Vec<Vec<Option<(&MigelItem, f64)>>> = products
+        .par_iter()
+        .map(|product| {
+            (0..variants.len())
+                .map(|idx| choose(idx, product))
+                .collect()
+        })
+        .collect();
+
+    let total = products.len();
+    let percent = |n: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            n as f64 * 100.0 / total as f64
+        }
+    };
+    let width = variants
+        .iter()
+        .map(|v| v.label.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+
+    println!(
+        "Compared {} products with {} matchers",
+        total,
+        variants.len()
+    );
+    println!("{:<width$}  matched", "matcher");
+    for (idx, variant) in variants.iter().enumerate() {
+        let matched = choices.iter().filter(|c| c[idx].is_some()).count();
+        println!(
+            "{:<width$}  {} ({:.1}%)",
+            variant.label,
+            matched,
+            percent(matched)
+        );
+    }
+
+    let disagreeing: Vec<usize> = (0..total)
+        .filter(|&p| {
+            choices[p]
+                .iter()
+                .any(|c| chosen(c) != chosen(&choices[p][0]))
+        })
+        .collect();
+    let agreeing = total - disagreeing.len();
+    println!(
+        "All matchers agree on {} of {} products ({:.1}%), {} disagree",
+        agreeing,
+        total,
+        percent(agreeing),
+        disagreeing.len()
+    );
+    if variants.len() > 2 {
+        println!("Pairwise agreement:");
+        for a in 0..variants.len() {
+            for b in a + 1..variants.len() {
+                let differ = choices
+                    .iter()
+                    .filter(|c| chosen(&c[a]) != chosen(&c[b]))
+                    .count();
+                println!(
+                    "  {} / {}: {:.1}% ({} disagree)",
+                    variants[a].label,
+                    variants[b].label,
+                    percent(total - differ),
+                    differ
+                );
+            }
+        }
+    }
+
+    if disagreeing.is_empty() {
+        return;
+    }
+    let shown = disagreeing.len().min(SAMPLE_SIZE);
+    println!("Disagreements (first {} of {}):", shown, disagreeing.len());
+    for &p in &disagreeing[..shown] {
+        println!("  {}  \"{}\"", products[p].gtin, products[p].description);
+        for (variant, choice) in variants.iter().zip(&choices[p]) {
+            match choice {
+                Some((item, score)) => println!(
+                    "    {:<width$}  {} {} ({:.2})",
+                    variant.label, item.position_nr, item.bezeichnung, score
+                ),
+                None => println!("    {:<width$}  unmatched", variant.label),
+            }
+        }
+    }
+}
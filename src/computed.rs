@@ -0,0 +1,127 @@
+use crate::identifiers::{
+    clean_gtin, find_identifier_column, gtin14, pharmacode, GTIN_HEADERS, PHARMACODE_HEADERS,
+};
+use crate::migel::{normalize_german, product_keywords};
+
+type ComputeFn = Box<dyn Fn(&[String]) -> String>;
+
+/// A derived column appended to every output row, computed from the
+/// (already capped) CSV row; columns found by header name are resolved
+/// against the capped header.
+pub struct ComputedColumn {
+    pub name: &'static str,
+    compute: ComputeFn,
+}
+
+/// Built-in computed columns selectable with `--computed-columns`.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ComputedKind {
+    /// `desc_normalized`: the German description (GS1 column 5) with umlauts
+    /// and accents folded by `normalize_german`
+    DescNormalized,
+    /// `gtin14`: the first column zero-padded to GTIN-14, empty unless it is
+    /// a GTIN with a valid check digit (`--normalize-gtin`)
+    Gtin14,
+    /// `product_keywords`: the keywords of the DE/FR/IT descriptions (GS1
+    /// columns 5-7) as extracted for MiGeL matching, space-separated
+    /// (`--product-keywords`)
+    ProductKeywords,
+    /// `pharmacode_clean`: the `PHARMACODE_HEADERS` column as seven digits
+    /// (`--clean-identifiers`); a malformed value is kept as is and flagged
+    PharmacodeClean,
+    /// `gtin_clean`: the `GTIN_HEADERS` column (else the first) as GTIN-13
+    /// (`--clean-identifiers`); a malformed value is kept as is and flagged
+    GtinClean,
+    /// `identifier_flags`: "invalid pharmacode" / "invalid gtin" for the
+    /// malformed values of the two columns above, empty when both are fine
+    IdentifierFlags,
+}
+
+/// The value in column `col` of a row, empty without the column.
+fn field(row: &[String], col: Option<usize>) -> &str {
+    col.and_then(|col| row.get(col)).map_or("", String::as_str)
+}
+
+/// `clean` applied to a value; a value it rejects is kept trimmed.
+fn cleaned(value: &str, clean: fn(&str) -> Option<String>) -> String {
+    clean(value).unwrap_or_else(|| value.trim().to_string())
+}
+
+/// Whether a non-empty value is rejected by `clean`.
+fn malformed(value: &str, clean: fn(&str) -> Option<String>) -> bool {
+    !value.trim().is_empty() && clean(value).is_none()
+}
+
+impl ComputedKind {
+    pub fn column(self, header: &[String]) -> ComputedColumn {
+        let gtin_col = Some(find_identifier_column(header, GTIN_HEADERS).unwrap_or(0));
+        let pharmacode_col = find_identifier_column(header, PHARMACODE_HEADERS);
+        match self {
+            ComputedKind::DescNormalized => ComputedColumn {
+                name: "desc_normalized",
+                compute: Box::new(|row| {
+                    normalize_german(row.get(5).map(String::as_str).unwrap_or(""))
+                }),
+            },
+            ComputedKind::Gtin14 => ComputedColumn {
+                name: "gtin14",
+                compute: Box::new(|row| row.first().and_then(|c| gtin14(c)).unwrap_or_default()),
+            },
+            ComputedKind::ProductKeywords => ComputedColumn {
+                name: "product_keywords",
+                compute: Box::new(|row| {
+                    let texts: Vec<&str> = (5..8)
+                        .map(|i| row.get(i).map(String::as_str).unwrap_or(""))
+                        .collect();
+                    product_keywords(&texts).join(" ")
+                }),
+            },
+            ComputedKind::PharmacodeClean => {
+                if pharmacode_col.is_none() {
+                    eprintln!(
+                        "Warning: no Pharmacode column among the output columns, \
+                         pharmacode_clean stays empty"
+                    );
+                }
+                ComputedColumn {
+                    name: "pharmacode_clean",
+                    compute: Box::new(move |row| cleaned(field(row, pharmacode_col), pharmacode)),
+                }
+            }
+            ComputedKind::GtinClean => ComputedColumn {
+                name: "gtin_clean",
+                compute: Box::new(move |row| cleaned(field(row, gtin_col), clean_gtin)),
+            },
+            ComputedKind::IdentifierFlags => ComputedColumn {
+                name: "identifier_flags",
+                compute: Box::new(move |row| {
+                    let mut flags = Vec::new();
+                    if malformed(field(row, pharmacode_col), pharmacode) {
+                        flags.push("invalid pharmacode");
+                    }
+                    if malformed(field(row, gtin_col), clean_gtin) {
+                        flags.push("invalid gtin");
+                    }
+                    flags.join(", ")
+                }),
+            },
+        }
+    }
+}
+
+/// Build the computed columns selected on the command line, in flag order,
+/// for the capped `header`.
+pub fn computed_columns(kinds: &[ComputedKind], header: &[String]) -> Vec<ComputedColumn> {
+    kinds.iter().map(|k| k.column(header)).collect()
+}
+
+/// Append the header names of `columns`.
+pub fn append_computed_headers(headers: &mut Vec<String>, columns: &[ComputedColumn]) {
+    headers.extend(columns.iter().map(|c| c.name.to_string()));
+}
+
+/// Append the values of `columns`, computed from the row before appending.
+pub fn append_computed(row: &mut Vec<String>, columns: &[ComputedColumn]) {
+    let values: Vec<String> = columns.iter().map(|c| (c.compute)(row)).collect();
+    row.extend(values);
+}
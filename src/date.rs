@@ -0,0 +1,96 @@
+use crate::report::Report;
+use chrono::NaiveDate;
+use serde_json::json;
+
+/// Parse the date of a feed value: ISO dates and timestamps
+/// (`2026-10-14`, `2026-10-14T08:30:00+02:00`), Swiss dates with optional
+/// time (`14.10.2026`, `14.10.26 08:30`) and compact `20261014`. The time
+/// of day and the offset are ignored.
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let date = text.trim().split(['T', ' ']).next()?;
+    if date.contains('.') {
+        let format = if date.rsplit('.').next()?.len() == 2 {
+            "%d.%m.%y"
+        } else {
+            "%d.%m.%Y"
+        };
+        NaiveDate::parse_from_str(date, format).ok()
+    } else if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) {
+        NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+    } else {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+    }
+}
+
+/// `--since`: keep rows whose date column is on or after `since`.
+pub struct DateFilter {
+    /// Index of the date column
+    pub column: usize,
+    pub since: NaiveDate,
+    /// `--include-undated`: keep rows with an empty or unparseable date
+    pub include_undated: bool,
+}
+
+/// Rows kept and skipped by a `DateFilter`.
+#[derive(Default)]
+pub struct DateCounts {
+    pub kept: usize,
+    pub older: usize,
+    pub undated: usize,
+}
+
+impl DateFilter {
+    /// Whether the row is processed.
+    pub fn keeps<'r>(&self, mut row: impl Iterator<Item = &'r str>) -> bool {
+        self.keeps_date(row.nth(self.column).and_then(parse_date))
+    }
+
+    /// Like `keeps`, counting the row in `counts`.
+    pub fn count<'r>(
+        &self,
+        mut row: impl Iterator<Item = &'r str>,
+        counts: &mut DateCounts,
+    ) -> bool {
+        let date = row.nth(self.column).and_then(parse_date);
+        match date {
+            Some(date) if date < self.since => counts.older += 1,
+            None => counts.undated += 1,
+            _ => {}
+        }
+        let keep = self.keeps_date(date);
+        if keep {
+            counts.kept += 1;
+        }
+        keep
+    }
+
+    fn keeps_date(&self, date: Option<NaiveDate>) -> bool {
+        match date {
+            Some(date) => date >= self.since,
+            None => self.include_undated,
+        }
+    }
+
+    /// Add the counts to the end-of-run summary.
+    pub fn report(&self, counts: &DateCounts, report: &mut Report) {
+        report.line(format!(
+            "Date filter (since {}): {} rows processed, {} older skipped, \
+             {} without a valid date {}",
+            self.since,
+            counts.kept,
+            counts.older,
+            counts.undated,
+            if self.include_undated { "included" } else { "skipped" }
+        ));
+        report.set(
+            "date_filter",
+            json!({
+                "since": self.since.to_string(),
+                "processed": counts.kept,
+                "older": counts.older,
+                "undated": counts.undated,
+                "include_undated": self.include_undated,
+            }),
+        );
+    }
+}
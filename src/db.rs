@@ -0,0 +1,665 @@
+use crate::cli::{DbArgs, OutputFormat};
+use crate::columnar::spawn_parquet_writer;
+use crate::error::Fb2SqliteError;
+use crate::interrupt;
+use crate::number::parse_swiss_number;
+use crate::renames::ColumnRenames;
+use chrono::Local;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+/// Output columns stored as REAL instead of TEXT (empty values become NULL).
+pub const REAL_COLUMNS: &[&str] = &["migel_betrag"];
+
+/// Name of the output table unless `--table-name` gives another.
+pub const DEFAULT_TABLE: &str = "data";
+
+/// The `--fts` full-text table of the output `table` (`data_fts` by default).
+pub fn fts_table(table: &str) -> String {
+    format!("{}_fts", table)
+}
+
+pub type ArchiveHandle = thread::JoinHandle<Result<usize, Box<dyn Error + Send + Sync>>>;
+pub type Archive = (String, mpsc::Sender<Vec<String>>, ArchiveHandle);
+
+/// Turn a CSV header into a SQLite column name.
+pub fn sanitize_column(header: &str) -> String {
+    header.replace(|c: char| !c.is_alphanumeric(), "_")
+}
+
+/// SQLite column names for a header row: sanitized (or as given by
+/// `--rename-columns`), with collisions (SQLite compares column names
+/// case-insensitively) renamed to `name_2`, `name_3`, ... so CREATE TABLE
+/// does not fail on a messy feed. With `report`, each rename and unused
+/// `--rename-columns` entry is logged (only done by the main writer, the
+/// archive writer sees the same header).
+pub fn column_names(headers: &[String], renames: &ColumnRenames, report: bool) -> Vec<String> {
+    let sanitized: Vec<String> = headers.iter().map(|h| sanitize_column(h)).collect();
+    if report {
+        let unused = renames.unused(&sanitized);
+        if !unused.is_empty() {
+            eprintln!(
+                "Warning: --rename-columns names columns not in the output: {}",
+                unused.join(", ")
+            );
+        }
+    }
+    let mut columns: Vec<String> = Vec::with_capacity(headers.len());
+    for (header, sanitized) in headers.iter().zip(sanitized) {
+        let base = renames
+            .get(&sanitized)
+            .map_or(sanitized.clone(), str::to_string);
+        let taken = |name: &str, columns: &[String]| {
+            columns.iter().any(|c| c.eq_ignore_ascii_case(name))
+        };
+        let mut name = base.clone();
+        let mut n = 2;
+        while taken(&name, &columns) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        if report && name != base {
+            eprintln!(
+                "Warning: duplicate column \"{}\" renamed to \"{}\"",
+                header, name
+            );
+        }
+        columns.push(name);
+    }
+    columns
+}
+
+/// `--prune-empty-columns`: records which of the first `width` columns hold
+/// a value in at least one data row. Later columns (computed, MiGeL) are
+/// always kept, so pruning happens after they are appended and indexes used
+/// to compute them stay valid.
+pub struct ColumnPruner {
+    used: Vec<bool>,
+}
+
+impl ColumnPruner {
+    pub fn new(width: usize) -> Self {
+        ColumnPruner {
+            used: vec![false; width],
+        }
+    }
+
+    /// Mark the non-empty values of a data row.
+    pub fn observe<'a>(&mut self, row: impl IntoIterator<Item = &'a str>) {
+        for (used, value) in self.used.iter_mut().zip(row) {
+            *used |= !value.trim().is_empty();
+        }
+    }
+
+    /// Log the dropped columns by header name.
+    pub fn report(&self, headers: &[String]) {
+        let pruned: Vec<&str> = headers
+            .iter()
+            .zip(&self.used)
+            .filter(|(_, &used)| !used)
+            .map(|(h, _)| h.as_str())
+            .collect();
+        if pruned.is_empty() {
+            println!("No empty columns to prune");
+        } else {
+            println!("Pruned {} empty columns: {}", pruned.len(), pruned.join(", "));
+        }
+    }
+
+    /// Drop the unused columns from a header or data row.
+    pub fn apply(&self, row: &mut Vec<String>) {
+        let mut idx = 0;
+        row.retain(|_| {
+            let keep = self.used.get(idx).copied().unwrap_or(true);
+            idx += 1;
+            keep
+        });
+    }
+}
+
+/// Number of inserted rows, or `None` when no header arrived and no table was created.
+pub type DbWriterHandle = thread::JoinHandle<Result<Option<usize>, Box<dyn Error + Send + Sync>>>;
+
+/// `--sqlite-cache-mb` / `--sqlite-mmap-mb` as PRAGMA statements for the
+/// connections writing the output database (empty without the flags, which
+/// leaves SQLite's defaults). Both trade memory for speed on big feeds: the
+/// cache holds that much of the database in RAM per connection, the mapping
+/// lets reads go through the OS page cache.
+pub fn tuning_pragmas(db: &DbArgs) -> String {
+    let mut pragmas = String::new();
+    if let Some(mb) = db.sqlite_cache_mb {
+        // A negative cache size is in KiB instead of pages
+        pragmas.push_str(&format!("PRAGMA cache_size = -{};", u64::from(mb) * 1024));
+    }
+    if let Some(mb) = db.sqlite_mmap_mb {
+        pragmas.push_str(&format!("PRAGMA mmap_size = {};", u64::from(mb) << 20));
+    }
+    pragmas
+}
+
+/// Spawn the writer for `--output-format`: `spawn_db_writer` or
+/// `columnar::spawn_parquet_writer`.
+pub fn spawn_output_writer(
+    path: String,
+    rx: mpsc::Receiver<Vec<String>>,
+    db: &DbArgs,
+    renames: &ColumnRenames,
+    fts_columns: Vec<String>,
+) -> DbWriterHandle {
+    match db.output_format {
+        OutputFormat::Sqlite => {
+            let (table, pragmas) = (db.table_name.clone(), tuning_pragmas(db));
+            spawn_db_writer(path, rx, table, renames.clone(), fts_columns, pragmas)
+        }
+        OutputFormat::Parquet => spawn_parquet_writer(path, rx, renames.clone()),
+    }
+}
+
+/// Spawn the thread that writes the output database: the first row received
+/// is the header, from which `table` (`--table-name`, a plain identifier) is
+/// (re)created; all further rows are inserted in a single transaction.
+/// Columns in `REAL_COLUMNS` are typed REAL, everything else TEXT. The header
+/// columns named in `fts_columns` (`--fts`) are indexed in `fts_table` in the
+/// same transaction.
+/// `pragmas` (`tuning_pragmas`) are run on the connection first.
+pub fn spawn_db_writer(
+    db_path: String,
+    rx: mpsc::Receiver<Vec<String>>,
+    table: String,
+    renames: ColumnRenames,
+    fts_columns: Vec<String>,
+    pragmas: String,
+) -> DbWriterHandle {
+    thread::spawn(move || -> Result<Option<usize>, Box<dyn Error + Send + Sync>> {
+        let mut conn = Connection::open(&db_path)?;
+        conn.execute_batch(&pragmas)?;
+        let tx_db = conn.transaction()?;
+        let mut inserted = None;
+
+        if let Ok(headers) = rx.recv() {
+            let real_cols: Vec<bool> = headers
+                .iter()
+                .map(|h| REAL_COLUMNS.contains(&h.as_str()))
+                .collect();
+            let columns = column_names(&headers, &renames, true);
+            let create_cols = columns
+                .iter()
+                .zip(&real_cols)
+                .map(|(c, &real)| format!("\"{}\" {}", c, if real { "REAL" } else { "TEXT" }))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // An index of a previous run would no longer match the new rows
+            let fts = fts_table(&table);
+            tx_db.execute(&format!("DROP TABLE IF EXISTS \"{}\"", fts), [])?;
+            tx_db.execute(&format!("DROP TABLE IF EXISTS \"{}\"", table), [])?;
+            tx_db.execute(&format!("CREATE TABLE \"{}\" ({})", table, create_cols), [])?;
+
+            let placeholders = vec!["?"; headers.len()].join(", ");
+            let query = format!("INSERT INTO \"{}\" VALUES ({})", table, placeholders);
+
+            let mut count = 0;
+            {
+                let mut stmt = tx_db.prepare(&query)?;
+                while let Ok(row) = rx.recv() {
+                    // REAL columns: empty (e.g. no amount) becomes NULL
+                    let values = row.into_iter().zip(&real_cols).map(|(v, &real)| {
+                        if real {
+                            parse_swiss_number(&v).map_or(Value::Null, Value::Real)
+                        } else {
+                            Value::Text(v)
+                        }
+                    });
+                    stmt.execute(rusqlite::params_from_iter(values))?;
+                    count += 1;
+                }
+            }
+            if let Some(gtin) = columns.first().filter(|_| !fts_columns.is_empty()) {
+                let indexed: Vec<&String> = fts_columns
+                    .iter()
+                    .filter_map(|name| headers.iter().position(|h| h == name))
+                    .map(|idx| &columns[idx])
+                    .collect();
+                create_fts_table(&tx_db, &table, gtin, &indexed)?;
+            }
+            inserted = Some(count);
+        }
+        if interrupt::interrupted() {
+            // Dropping the transaction rolls back: the previous table stays
+            return Err(Fb2SqliteError::Interrupted.into());
+        }
+        tx_db.commit()?;
+        Ok(inserted)
+    })
+}
+
+/// `--fts`: an FTS5 table `fts_table` with the first (GTIN) column, unindexed,
+/// to join back to `table`, and a copy of the `indexed` text columns (`table`
+/// has no stable rowid to refer to, VACUUM may renumber it). Diacritics are
+/// removed by the tokenizer, so `katheter` is found in any spelling.
+fn create_fts_table(
+    tx_db: &rusqlite::Transaction,
+    table: &str,
+    gtin: &str,
+    indexed: &[&String],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if indexed.is_empty() {
+        eprintln!("Warning: --fts found none of the description columns, no index built");
+        return Ok(());
+    }
+    let quoted: Vec<String> = indexed.iter().map(|c| format!("\"{}\"", c)).collect();
+    let fts = fts_table(table);
+    tx_db.execute(
+        &format!(
+            "CREATE VIRTUAL TABLE \"{}\" USING fts5(\"{}\" UNINDEXED, {}, \
+             tokenize = 'unicode61 remove_diacritics 2')",
+            fts,
+            gtin,
+            quoted.join(", ")
+        ),
+        [],
+    )?;
+    tx_db.execute(
+        &format!(
+            "INSERT INTO \"{}\" SELECT \"{}\", {} FROM \"{}\"",
+            fts,
+            gtin,
+            quoted.join(", "),
+            table
+        ),
+        [],
+    )?;
+    let names: Vec<&str> = indexed.iter().map(|c| c.as_str()).collect();
+    println!(
+        "Indexed {} for full-text search in {}",
+        names.join(", "),
+        fts
+    );
+    Ok(())
+}
+
+/// `--fts`: fail before any work if the linked SQLite has no FTS5.
+pub fn check_fts5() -> Result<(), Box<dyn Error>> {
+    let probe = Connection::open_in_memory()?.execute_batch("CREATE VIRTUAL TABLE t USING fts5(x)");
+    if probe.is_err() {
+        return Err(Fb2SqliteError::Sqlite(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+            Some("--fts needs the FTS5 extension, which the linked SQLite library lacks".into()),
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Wait for the database writer and return the number of inserted rows.
+/// Fails when no table was created (the input had no header row), so an
+/// empty run is not reported as a success or uploaded.
+pub fn join_db_writer(handle: DbWriterHandle, db_path: &str) -> Result<usize, Box<dyn Error>> {
+    let inserted = handle
+        .join()
+        .map_err(|_| "The database thread panicked")?
+        .map_err(|e| -> Box<dyn Error> { e })?;
+    inserted.ok_or_else(|| {
+        Fb2SqliteError::Validation(format!(
+            "No table written to {}: the CSV has no header row",
+            db_path
+        ))
+        .into()
+    })
+}
+
+/// Spawn a thread that appends rows to the `data` table of a persistent archive
+/// database, tagged with today's `run_date`. Expects the header row first.
+/// The table (and the `(gtin, run_date)` index) is created only if missing; columns
+/// new to the archive are added. Rows of an earlier run on the same day are replaced.
+/// Returns the number of appended rows.
+fn spawn_archive_writer(
+    path: String,
+    rx: mpsc::Receiver<Vec<String>>,
+    renames: ColumnRenames,
+) -> ArchiveHandle {
+    thread::spawn(move || -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let run_date = Local::now().format("%Y-%m-%d").to_string();
+        let mut conn = Connection::open(&path)?;
+        let tx_db = conn.transaction()?;
+        let mut appended = 0;
+
+        if let Ok(headers) = rx.recv() {
+            let columns = column_names(&headers, &renames, false);
+            let create_cols = columns
+                .iter()
+                .map(|c| format!("\"{}\" TEXT", c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            tx_db.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS data ({}, \"run_date\" TEXT)",
+                    create_cols
+                ),
+                [],
+            )?;
+
+            let existing: Vec<String> = {
+                let mut stmt = tx_db.prepare("SELECT name FROM pragma_table_info('data')")?;
+                let names = stmt.query_map([], |r| r.get(0))?;
+                names.collect::<Result<_, _>>()?
+            };
+            for column in &columns {
+                if !existing.iter().any(|e| e.eq_ignore_ascii_case(column)) {
+                    tx_db.execute(
+                        &format!("ALTER TABLE data ADD COLUMN \"{}\" TEXT", column),
+                        [],
+                    )?;
+                }
+            }
+
+            // First column is the GTIN in the GS1 feed
+            if let Some(gtin) = columns.first() {
+                tx_db.execute(
+                    &format!(
+                        "CREATE INDEX IF NOT EXISTS idx_data_gtin_run_date ON data (\"{}\", run_date)",
+                        gtin
+                    ),
+                    [],
+                )?;
+            }
+            tx_db.execute("DELETE FROM data WHERE run_date = ?1", [&run_date])?;
+
+            let column_list = columns
+                .iter()
+                .map(|c| format!("\"{}\"", c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let placeholders = vec!["?"; columns.len() + 1].join(", ");
+            let query = format!(
+                "INSERT INTO data ({}, \"run_date\") VALUES ({})",
+                column_list, placeholders
+            );
+
+            {
+                let mut stmt = tx_db.prepare(&query)?;
+                while let Ok(mut row) = rx.recv() {
+                    row.resize(columns.len(), String::new());
+                    row.push(run_date.clone());
+                    stmt.execute(rusqlite::params_from_iter(row))?;
+                    appended += 1;
+                }
+            }
+        }
+        if interrupt::interrupted() {
+            return Err(Fb2SqliteError::Interrupted.into());
+        }
+        tx_db.commit()?;
+        Ok(appended)
+    })
+}
+
+/// Start an archive writer if `--archive` was given.
+pub fn start_archive(path: &Option<String>, renames: &ColumnRenames) -> Option<Archive> {
+    path.as_ref().map(|path| {
+        let (archive_tx, archive_rx) = mpsc::channel::<Vec<String>>();
+        let handle = spawn_archive_writer(path.clone(), archive_rx, renames.clone());
+        (path.clone(), archive_tx, handle)
+    })
+}
+
+/// Wait for the archive writer and report the result.
+pub fn finish_archive(archive: Option<Archive>) -> Result<(), Box<dyn Error>> {
+    if let Some((path, archive_tx, handle)) = archive {
+        drop(archive_tx);
+        let appended = handle
+            .join()
+            .map_err(|_| "The archive thread panicked")?
+            .map_err(|e| -> Box<dyn Error> { e })?;
+        println!("Archived {} rows to {}", appended, path);
+    }
+    Ok(())
+}
+
+/// The file a run writes its database into: `<db_path>.tmp` with
+/// `--temp-db` (leftovers of a crashed run are removed first), otherwise
+/// `db_path`. `publish_db` moves it into place once it is complete.
+pub fn build_path(db_path: &str, db: &DbArgs) -> Result<String, Box<dyn Error>> {
+    if !db.temp_db || db.to_stdout() {
+        return Ok(db_path.to_string());
+    }
+    let temp_path = format!("{}.tmp", db_path);
+    for stale in [temp_path.clone(), format!("{}-journal", temp_path)] {
+        if Path::new(&stale).exists() {
+            fs::remove_file(&stale)?;
+        }
+    }
+    Ok(temp_path)
+}
+
+/// Atomically rename a database built at `build_path` to `db_path`; readers
+/// of `db_path` see either the previous or the complete new file.
+pub fn publish_db(build_path: &str, db_path: &str) -> Result<(), Box<dyn Error>> {
+    if build_path != db_path {
+        fs::rename(build_path, db_path)?;
+        println!("Renamed {} to {}", build_path, db_path);
+    }
+    Ok(())
+}
+
+/// Optional post-processing of the finished database: ANALYZE (`--analyze`)
+/// so consumers' query planner has statistics, then VACUUM (`--vacuum`).
+pub fn finish_db(path: &str, db: &DbArgs) -> Result<(), Box<dyn Error>> {
+    let open = || -> Result<Connection, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(&tuning_pragmas(db))?;
+        Ok(conn)
+    };
+    if db.analyze {
+        open()?.execute_batch("ANALYZE")?;
+        println!("Gathered query planner statistics for {}", path);
+    }
+    if db.vacuum {
+        let before = fs::metadata(path)?.len();
+        open()?.execute_batch("VACUUM")?;
+        let after = fs::metadata(path)?.len();
+        println!("Vacuumed {}: {} -> {} bytes", path, before, after);
+    }
+    Ok(())
+}
+
+/// One `--audit` row: what the matcher decided for a product and why.
+pub struct AuditEntry {
+    pub gtin: String,
+    /// The DE/FR/IT description and brand texts that were scored
+    pub product_text: String,
+    /// The matched item, `None` (NULL) for a non-match
+    pub position_nr: Option<String>,
+    pub score: Option<f64>,
+    pub matched_keywords: String,
+    /// `matched`, `no_match`, `no_description`, `too_few_tokens` or `blocked`
+    pub decision: &'static str,
+}
+
+/// Write the `--audit` entries into the `match_audit` table, replacing the
+/// previous run's entries. Every row carries the `--min-score` threshold,
+/// the fb2sqlite version and the time of writing, so a database shows how
+/// its MiGeL codes were assigned.
+pub fn write_match_audit(
+    db_filename: &str,
+    entries: &[AuditEntry],
+    threshold: f64,
+) -> Result<(), Box<dyn Error>> {
+    let run_ts = Local::now().format("%Y-%m-%dT%H:%M:%S%:z").to_string();
+    let mut conn = Connection::open(db_filename)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(
+        "DROP TABLE IF EXISTS match_audit;
+         CREATE TABLE match_audit (gtin TEXT, product_text TEXT, position_nr TEXT, score REAL, \
+         matched_keywords TEXT, threshold REAL, tool_version TEXT, run_ts TEXT, decision TEXT);",
+    )?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO match_audit VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+        for entry in entries {
+            stmt.execute(rusqlite::params![
+                entry.gtin,
+                entry.product_text,
+                entry.position_nr,
+                entry.score,
+                entry.matched_keywords,
+                threshold,
+                env!("CARGO_PKG_VERSION"),
+                run_ts,
+                entry.decision,
+            ])?;
+        }
+    }
+    tx.execute("CREATE INDEX idx_match_audit_gtin ON match_audit (gtin)", [])?;
+    tx.commit()?;
+    println!("Wrote {} matching decisions to match_audit", entries.len());
+    Ok(())
+}
+
+/// Write the `--multi-match` links (gtin, migel_code, score, rank) into the
+/// `product_migel` table, replacing the previous run's links.
+pub fn write_product_migel(
+    db_filename: &str,
+    links: &[(String, String, f64, usize)],
+) -> Result<(), Box<dyn Error>> {
+    let mut conn = Connection::open(db_filename)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(
+        "DROP TABLE IF EXISTS product_migel;
+         CREATE TABLE product_migel (gtin TEXT, migel_code TEXT, score REAL, rank INTEGER);",
+    )?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO product_migel VALUES (?, ?, ?, ?)")?;
+        for (gtin, code, score, rank) in links {
+            stmt.execute(rusqlite::params![gtin, code, score, *rank as i64])?;
+        }
+    }
+    tx.execute("CREATE INDEX idx_product_migel_gtin ON product_migel (gtin)", [])?;
+    tx.commit()?;
+    println!("Wrote {} product/MiGeL links to product_migel", links.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database path in the temp directory, removed first.
+    fn temp_db(name: &str) -> String {
+        let file = format!("fb2sqlite-{}-{}.db", name, std::process::id());
+        let path = std::env::temp_dir().join(file);
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn strings(row: &[&str]) -> Vec<String> {
+        row.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn db_writer_creates_table_from_header_and_inserts_rows() {
+        let path = temp_db("writer");
+        let (tx, rx) = mpsc::channel();
+        let handle = spawn_db_writer(
+            path.clone(),
+            rx,
+            DEFAULT_TABLE.to_string(),
+            ColumnRenames::default(),
+            Vec::new(),
+            String::new(),
+        );
+        for row in [
+            ["GTIN", "Bezeichnung", "migel_betrag"],
+            ["07612345000011", "Gaze", "1'234,50"],
+            ["07612345000028", "Katheter", ""],
+        ] {
+            tx.send(strings(&row)).unwrap();
+        }
+        drop(tx);
+        assert_eq!(join_db_writer(handle, &path).unwrap(), 2);
+
+        let conn = Connection::open(&path).unwrap();
+        let rows: Vec<(String, String, Option<f64>)> = conn
+            .prepare("SELECT GTIN, Bezeichnung, migel_betrag FROM data ORDER BY GTIN")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("07612345000011".into(), "Gaze".into(), Some(1234.5)),
+                ("07612345000028".into(), "Katheter".into(), None),
+            ]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn db_writer_without_header_writes_no_table() {
+        let path = temp_db("writer-empty");
+        let (tx, rx) = mpsc::channel::<Vec<String>>();
+        let handle = spawn_db_writer(
+            path.clone(),
+            rx,
+            DEFAULT_TABLE.to_string(),
+            ColumnRenames::default(),
+            Vec::new(),
+            String::new(),
+        );
+        drop(tx);
+        assert!(join_db_writer(handle, &path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn duplicate_headers_get_numbered_columns() {
+        let headers = strings(&["GTIN", "Name", "name", "Name", "Preis (CHF)"]);
+        let columns = column_names(&headers, &ColumnRenames::default(), false);
+        assert_eq!(columns, ["GTIN", "Name", "name_2", "Name_3", "Preis__CHF_"]);
+
+        let path = temp_db("duplicates");
+        let (tx, rx) = mpsc::channel();
+        let handle = spawn_db_writer(
+            path.clone(),
+            rx,
+            DEFAULT_TABLE.to_string(),
+            ColumnRenames::default(),
+            Vec::new(),
+            String::new(),
+        );
+        tx.send(headers).unwrap();
+        tx.send(strings(&[
+            "07612345000011",
+            "Gaze",
+            "Kompresse",
+            "steril",
+            "3,20",
+        ]))
+        .unwrap();
+        drop(tx);
+        assert_eq!(join_db_writer(handle, &path).unwrap(), 1);
+
+        let conn = Connection::open(&path).unwrap();
+        let table: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('data')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(table, columns);
+        let row: (String, String, String) = conn
+            .query_row("SELECT Name, name_2, Name_3 FROM data", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(row, ("Gaze".into(), "Kompresse".into(), "steril".into()));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use crate::upload::{find_program, OPENSSH_HINT};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use rusqlite::Connection;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Time allowed for each HEAD request; a real download may take much longer.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Scratch file for the write and SQLite checks, removed again.
+const PROBE_FILE: &str = ".fb2sqlite_doctor.db";
+
+/// `doctor`: check the prerequisites of a run and print one pass/fail line
+/// per check: the upload programs, a HEAD request for each of `urls`
+/// (label, URL), a file written to the work directory and a SQLite database
+/// opened there. Fails after printing all checks if any of them failed.
+pub fn run_doctor(
+    urls: &[(&str, &str)],
+    work_dir: &Path,
+    client: &Client,
+) -> Result<(), Box<dyn Error>> {
+    let mut checks: Vec<(String, Result<String, String>)> = Vec::new();
+    for program in ["scp", "ssh"] {
+        let result = find_program(program)
+            .map(|path| path.display().to_string())
+            .ok_or_else(|| format!("not found on PATH; {}", OPENSSH_HINT));
+        checks.push((program.to_string(), result));
+    }
+    for &(label, url) in urls {
+        checks.push((format!("{} {}", label, url), head_request(url, client)));
+    }
+    let probe = work_dir.join(PROBE_FILE);
+    let dir = if work_dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        work_dir
+    };
+    checks.push((
+        format!("work directory {}", dir.display()),
+        fs::write(&probe, b"")
+            .map(|_| "writable".to_string())
+            .map_err(|e| e.to_string()),
+    ));
+    checks.push(("SQLite".to_string(), sqlite_probe(&probe)));
+    let _ = fs::remove_file(&probe);
+
+    let mut failed = 0;
+    for (name, result) in &checks {
+        match result {
+            Ok(detail) => println!("[ OK ] {}: {}", name, detail),
+            Err(detail) => {
+                failed += 1;
+                println!("[FAIL] {}: {}", name, detail);
+            }
+        }
+    }
+    let passed = checks.len() - failed;
+    println!("{} of {} checks passed", passed, checks.len());
+    if failed > 0 {
+        return Err(format!("{} of {} doctor checks failed", failed, checks.len()).into());
+    }
+    Ok(())
+}
+
+/// HEAD `url` with the run's client (user agent, `--header`s). Any answer
+/// below 400 counts as reachable, as do 405 and 501 from servers that do not
+/// implement HEAD.
+fn head_request(url: &str, client: &Client) -> Result<String, String> {
+    let response = client
+        .head(url)
+        .timeout(HEAD_TIMEOUT)
+        .send()
+        .map_err(|e| error_chain(&e.without_url()))?;
+    let status = response.status();
+    let no_head = [StatusCode::METHOD_NOT_ALLOWED, StatusCode::NOT_IMPLEMENTED];
+    if status.as_u16() < 400 || no_head.contains(&status) {
+        Ok(format!("HTTP {}", status))
+    } else {
+        Err(format!("HTTP {}", status))
+    }
+}
+
+/// An error with its causes ("error sending request: ... : dns error ..."),
+/// since the top-level message of a failed request names no reason.
+fn error_chain(e: &dyn Error) -> String {
+    let mut text = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        text.push_str(": ");
+        text.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    text
+}
+
+/// Create, fill and read a table in a SQLite database at `path`.
+fn sqlite_probe(path: &Path) -> Result<String, String> {
+    let probe = || -> rusqlite::Result<i64> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("CREATE TABLE probe (x INTEGER); INSERT INTO probe VALUES (1);")?;
+        conn.query_row("SELECT count(*) FROM probe", [], |row| row.get(0))
+    };
+    probe()
+        .map(|_| format!("version {}", rusqlite::version()))
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,132 @@
+use crate::error::Fb2SqliteError;
+use crate::interrupt;
+use crate::retry::RETRY_DELAY;
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::error::Error;
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+/// Default `User-Agent` of the feed and MiGeL downloads (`--user-agent`).
+pub const USER_AGENT: &str = "fb2sqlite/0.1";
+
+/// Upper bound for one download; the full GS1 feed takes a few minutes.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Headers worth showing when a download fails (rate limits, moved files, auth).
+const DEBUG_HEADERS: &[&str] = &["content-type", "content-length", "retry-after", "location"];
+
+/// Bytes of the error body included in the message.
+const BODY_PREVIEW_LEN: usize = 200;
+
+/// Times a download answered with HTTP 429 (Too Many Requests) is repeated.
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` delay that is waited for; a server asking for more
+/// is reported as a failed download instead of blocking the run.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(600);
+
+/// Send a download request and pass the response through `check_response`.
+/// HTTP 429 is retried up to `RATE_LIMIT_RETRIES` times after the delay of
+/// its `Retry-After` header (seconds or an HTTP date), or the linear
+/// `RETRY_DELAY` backoff without one.
+pub fn send_checked(request: RequestBuilder, what: &str) -> Result<Response, Box<dyn Error>> {
+    let mut attempt = 0;
+    loop {
+        // Download requests have no body, so they can always be cloned
+        let Some(retry) = request.try_clone() else {
+            return Err("Download request cannot be cloned".into());
+        };
+        let response = retry.send()?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS
+            || attempt == RATE_LIMIT_RETRIES
+            || interrupt::interrupted()
+        {
+            return check_response(response, what);
+        }
+        attempt += 1;
+        let delay = match response.headers().get(RETRY_AFTER) {
+            Some(value) => retry_after(value).unwrap_or(RETRY_DELAY * attempt),
+            None => RETRY_DELAY * attempt,
+        };
+        if delay > MAX_RATE_LIMIT_WAIT {
+            return check_response(response, what);
+        }
+        eprintln!(
+            "{} rate limited (HTTP 429), retry {}/{} in {}s",
+            what,
+            attempt,
+            RATE_LIMIT_RETRIES,
+            delay.as_secs()
+        );
+        thread::sleep(delay);
+    }
+}
+
+/// The delay of a `Retry-After` value: delta seconds or an HTTP date (a date
+/// in the past means no delay). `None` if it is neither.
+fn retry_after(value: &HeaderValue) -> Option<Duration> {
+    let value = value.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&Utc) - Utc::now();
+    Some(wait.to_std().unwrap_or_default())
+}
+
+/// Pass a successful response through; otherwise return an error with the
+/// status line, the headers in `DEBUG_HEADERS` and the start of the body.
+fn check_response(response: Response, what: &str) -> Result<Response, Box<dyn Error>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let mut message = format!(
+        "Failed to download {}: HTTP {} ({})",
+        what,
+        status,
+        response.url()
+    );
+    for name in DEBUG_HEADERS {
+        if let Some(value) = response.headers().get(*name) {
+            message.push_str(&format!(
+                "; {}: {}",
+                name,
+                String::from_utf8_lossy(value.as_bytes())
+            ));
+        }
+    }
+    let mut body = Vec::new();
+    // The body is only a hint, a read error must not hide the status
+    let _ = response.take(BODY_PREVIEW_LEN as u64).read_to_end(&mut body);
+    // Collapse whitespace so the preview stays on one line
+    let body = String::from_utf8_lossy(&body)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !body.is_empty() {
+        message.push_str(&format!("; body: {}", body));
+    }
+    Err(Fb2SqliteError::Download(message).into())
+}
+
+/// The client of all downloads, sending `user_agent` and the `--header`
+/// values with every request.
+pub fn http_client(
+    user_agent: &str,
+    headers: &[(HeaderName, HeaderValue)],
+) -> Result<Client, Box<dyn Error>> {
+    let mut default_headers = HeaderMap::new();
+    for (name, value) in headers {
+        default_headers.insert(name.clone(), value.clone());
+    }
+    Ok(Client::builder()
+        .user_agent(user_agent)
+        .default_headers(default_headers)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()?)
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+use thiserror::Error;
+
+// Process exit codes; 2 is taken by clap's usage errors
+/// Any error outside the categories below (e.g. a missing local file)
+pub const EXIT_OTHER: u8 = 1;
+pub const EXIT_DOWNLOAD: u8 = 3;
+pub const EXIT_PARSE: u8 = 4;
+pub const EXIT_SQLITE: u8 = 5;
+pub const EXIT_UPLOAD: u8 = 6;
+pub const EXIT_VALIDATION: u8 = 7;
+/// 128 + SIGINT, as a shell reports a process killed by Ctrl-C
+pub const EXIT_INTERRUPTED: u8 = 130;
+/// `--max-runtime` exceeded; the code of coreutils `timeout`
+pub const EXIT_TIMEOUT: u8 = 124;
+
+/// Failure categories of a run, each with its own process exit code (see
+/// `exit_code`). Functions keep returning `Box<dyn Error>`; they raise these
+/// where a failure is detected, and `exit_code` also recognizes the errors
+/// of the download, parsing and SQLite libraries.
+#[derive(Debug, Error)]
+pub enum Fb2SqliteError {
+    /// Fetching the product feed or the MiGeL list failed
+    #[error("{0}")]
+    Download(String),
+    /// The feed, the MiGeL XLSX or another input file could not be read
+    #[error("{0}")]
+    Parse(String),
+    /// Writing or post-processing a database failed
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    /// Transfer, verification or schema check of the upload failed
+    #[error("{0}")]
+    Upload(String),
+    /// Input was rejected (options, column selection, truncated feed, ...)
+    #[error("{0}")]
+    Validation(String),
+    /// Ctrl-C stopped the run; the databases were rolled back
+    #[error("Interrupted by Ctrl-C, no database was changed")]
+    Interrupted,
+    /// `--max-runtime` (seconds) was exceeded and the run was stopped
+    #[error("Run exceeded --max-runtime of {0}s and was aborted")]
+    Timeout(u64),
+}
+
+impl Fb2SqliteError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Fb2SqliteError::Download(_) => EXIT_DOWNLOAD,
+            Fb2SqliteError::Parse(_) => EXIT_PARSE,
+            Fb2SqliteError::Sqlite(_) => EXIT_SQLITE,
+            Fb2SqliteError::Upload(_) => EXIT_UPLOAD,
+            Fb2SqliteError::Validation(_) => EXIT_VALIDATION,
+            Fb2SqliteError::Interrupted => EXIT_INTERRUPTED,
+            Fb2SqliteError::Timeout(_) => EXIT_TIMEOUT,
+        }
+    }
+}
+
+/// Process exit code for an error that ended the run.
+pub fn exit_code(e: &(dyn Error + 'static)) -> u8 {
+    if let Some(e) = e.downcast_ref::<Fb2SqliteError>() {
+        e.exit_code()
+    } else if e.is::<reqwest::Error>() {
+        EXIT_DOWNLOAD
+    } else if e.is::<csv::Error>() || e.is::<serde_json::Error>() || e.is::<calamine::XlsxError>()
+    {
+        EXIT_PARSE
+    } else if e.is::<rusqlite::Error>() {
+        EXIT_SQLITE
+    } else {
+        EXIT_OTHER
+    }
+}
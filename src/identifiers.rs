@@ -0,0 +1,75 @@
+/// Check a GTIN-8/12/13/14 check digit (mod 10, weights 3 and 1 alternating
+/// from the right, the check digit itself excluded).
+pub fn is_valid_gtin(code: &str) -> bool {
+    if !matches!(code.len(), 8 | 12 | 13 | 14) || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let digits: Vec<u32> = code.bytes().map(|b| u32::from(b - b'0')).collect();
+    let (check, body) = digits.split_last().expect("GTIN has at least 8 digits");
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d })
+        .sum();
+    (10 - sum % 10) % 10 == *check
+}
+
+/// Left-pad a valid GTIN to 14 digits; `None` for anything that is not a
+/// GTIN with a correct check digit (those are left alone).
+pub fn gtin14(code: &str) -> Option<String> {
+    let code = code.trim();
+    is_valid_gtin(code).then(|| format!("{:0>14}", code))
+}
+
+/// Lookup key of a GTIN: GTIN-13 and zero-padded GTIN-14 spellings of a code
+/// are the same product, and the check digit is not required to be valid.
+pub fn gtin_key(code: &str) -> String {
+    code.trim().trim_start_matches('0').to_string()
+}
+
+/// Header names of Pharmacode columns, compared by `header_key`.
+pub const PHARMACODE_HEADERS: &[&str] = &["pharmacode", "pharmacodech", "phar", "pharmacodenr"];
+
+/// Header names of GTIN columns, compared by `header_key`; without one the
+/// first column is the GTIN, as everywhere else.
+pub const GTIN_HEADERS: &[&str] = &["gtin", "gtin13", "gtin14", "ean", "ean13", "eancode"];
+
+/// A header reduced to its lowercase letters and digits ("Pharmacode-CH" is
+/// "pharmacodech").
+fn header_key(header: &str) -> String {
+    header
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Index of the first header that is one of `names`.
+pub fn find_identifier_column(header: &[String], names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|h| names.contains(&header_key(h).as_str()))
+}
+
+/// A Swiss Pharmacode: one to seven digits, zero-padded to seven (feeds drop
+/// the leading zeros); `None` for anything else.
+pub fn pharmacode(code: &str) -> Option<String> {
+    let code = code.trim();
+    let valid = matches!(code.len(), 1..=7)
+        && code.bytes().all(|b| b.is_ascii_digit())
+        && code.bytes().any(|b| b != b'0');
+    valid.then(|| format!("{:0>7}", code))
+}
+
+/// A valid GTIN in the form of the Swiss drug datasets: 13 digits (GTIN-8
+/// and -12 zero-padded, a GTIN-14 without its leading zero), a GTIN-14 with
+/// another indicator digit as is; `None` for an invalid code.
+pub fn clean_gtin(code: &str) -> Option<String> {
+    let code = code.trim();
+    let code = match code.strip_prefix('0') {
+        Some(rest) if code.len() == 14 => rest,
+        _ => code,
+    };
+    is_valid_gtin(code).then(|| format!("{:0>13}", code))
+}
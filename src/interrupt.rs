@@ -0,0 +1,36 @@
+use crate::error::{Fb2SqliteError, EXIT_INTERRUPTED};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the first Ctrl-C.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle Ctrl-C during `import` and `migel`: the first press stops the
+/// rows, the writer threads then roll back instead of committing and the run
+/// ends with `Fb2SqliteError::Interrupted`. A second press exits at once.
+pub fn install() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_INTERRUPTED.into());
+        }
+        eprintln!("Interrupted, rolling back (press Ctrl-C again to quit immediately)");
+    })
+}
+
+/// Stop the run as if Ctrl-C was pressed (`--max-runtime`).
+pub fn stop() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether Ctrl-C was pressed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// `Interrupted` once Ctrl-C was pressed, for checks between steps.
+pub fn check() -> Result<(), Fb2SqliteError> {
+    if interrupted() {
+        Err(Fb2SqliteError::Interrupted)
+    } else {
+        Ok(())
+    }
+}
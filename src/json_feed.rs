@@ -0,0 +1,49 @@
+use crate::error::Fb2SqliteError;
+use serde_json::Value;
+use std::error::Error;
+
+/// Convert a JSON product feed into CSV text, so JSON input runs through the
+/// same record handling as the CSV feed. Accepts a top-level array of objects
+/// or an object holding one (e.g. `{"items": [...]}`). The header is the
+/// union of all field names in first-seen order; missing fields and `null`
+/// become empty cells, nested values are kept as JSON text.
+pub fn json_to_csv(text: &str) -> Result<String, Box<dyn Error>> {
+    let value: Value = serde_json::from_str(text)?;
+    let records = match &value {
+        Value::Array(records) => records,
+        Value::Object(fields) => fields
+            .values()
+            .find_map(Value::as_array)
+            .ok_or_else(|| {
+                Fb2SqliteError::Parse("JSON feed object contains no array of records".into())
+            })?,
+        _ => {
+            return Err(
+                Fb2SqliteError::Parse("JSON feed is neither an array nor an object".into()).into(),
+            )
+        }
+    };
+
+    let mut columns: Vec<&str> = Vec::new();
+    for record in records {
+        let fields = record
+            .as_object()
+            .ok_or_else(|| Fb2SqliteError::Parse("JSON feed record is not an object".into()))?;
+        for name in fields.keys() {
+            if !columns.contains(&name.as_str()) {
+                columns.push(name);
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&columns)?;
+    for record in records {
+        writer.write_record(columns.iter().map(|name| match record.get(*name) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+        }))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
@@ -1,186 +1,1129 @@
+mod abbreviations;
+mod blocklist;
+mod cli;
+mod columnar;
+mod compare;
+mod computed;
+mod date;
+mod db;
+mod doctor;
+mod download;
+mod error;
+mod identifiers;
+mod interrupt;
+mod json_feed;
 mod migel;
+mod number;
+mod overrides;
+mod output;
+mod phonetic;
+mod renames;
+mod report;
+mod retry;
+mod sources;
+mod synonyms;
+mod upload;
+mod watchdog;
 
+use blocklist::Blocklist;
 use chrono::Local;
 use clap::Parser;
-use csv::ReaderBuilder;
-use migel::{build_keyword_index, find_best_migel_match, parse_migel_items, MigelItem};
+use cli::{
+    Cli, ColumnArgs, Command, CsvEncoding, DbArgs, ExplainArgs, ImportArgs, InputArgs,
+    InputFormat, MatchArgs, MatchCommandArgs, MigelArgs, OutputFormat, SinceArgs, TuneArgs,
+};
+use compare::{compare_matchers, Product};
+use computed::{append_computed, append_computed_headers, computed_columns};
+use csv::{ReaderBuilder, StringRecord};
+use date::{DateCounts, DateFilter};
+use db::{
+    build_path, check_fts5, finish_archive, finish_db, join_db_writer, publish_db,
+    spawn_output_writer, start_archive, write_match_audit, write_product_migel, AuditEntry,
+    ColumnPruner, DEFAULT_TABLE,
+};
+use download::{http_client, send_checked};
+use error::Fb2SqliteError;
+use identifiers::gtin_key;
+use migel::{
+    build_keyword_index, find_best_migel_match, highlight_keywords, items_without_keywords,
+    keyword_postings, list_sheets, load_keyword_supplements, merge_editions, parse_migel_csv,
+    parse_migel_items, parse_migel_seed, product_token_count, rank_migel_matches, single_line,
+    KeywordIndex, Language, MatchOptions, MigelItem, ParseOptions,
+};
+use output::StdoutSink;
+use overrides::{Decision, Overrides};
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::SeedableRng;
 use rayon::prelude::*;
-use rusqlite::Connection;
-use std::collections::HashMap;
+use report::{Report, ReportFormat};
+use reqwest::blocking::Client;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fs;
-use std::io::{Cursor, Write};
-use std::process::Command;
+use std::io::{self, BufRead, BufWriter, Cursor, IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
 use std::sync::mpsc;
-use std::thread;
+use upload::{sha256_hex, upload_db};
 
-#[derive(Parser)]
-#[command(name = "fb2sqlite")]
-struct Args {
-    /// Download MiGeL XLSX and map migel codes/limitations to products
-    #[arg(long)]
-    migel: bool,
+/// Parsed MiGeL items and their keyword index.
+type MigelData = (Vec<MigelItem>, KeywordIndex);
 
-    /// Use local firstbase.csv instead of downloading (useful when GS1 server is slow)
-    #[arg(long)]
-    local_csv: bool,
+/// MiGeL workbook paths with their edition label (`--migel-file`).
+type MigelFiles = Vec<(Option<String>, String)>;
 
-    /// Deploy: SCP the database to the remote server (uses plain filename without date)
-    #[arg(long)]
-    deploy: bool,
+/// Number of CSV columns written to the `data` table.
+const MAX_COLUMNS: usize = 15;
+
+/// Cut a row to `MAX_COLUMNS`. A merged multi-source feed keeps its trailing
+/// `source` column, which would otherwise fall beyond the cap.
+fn cap_columns(row: &mut Vec<String>, keep_source: bool) {
+    let source = if keep_source { row.pop() } else { None };
+    row.truncate(MAX_COLUMNS);
+    row.extend(source);
 }
 
-fn run_normal(csv_content: &str) -> Result<(), Box<dyn Error>> {
-    let db_filename = "firstbase.db";
+/// The CSV columns written to the output table: the first `MAX_COLUMNS` (see
+/// `cap_columns`), or with `--columns` the selected ones in the given order.
+enum OutputColumns {
+    Capped { keep_source: bool },
+    Selected(Vec<usize>),
+}
 
-    let (tx, rx) = mpsc::channel::<Vec<String>>();
+impl OutputColumns {
+    /// Resolve `--columns` against the CSV header like `resolve_column`; a
+    /// column may be selected only once.
+    fn new(
+        header: &[String],
+        columns: &[String],
+        keep_source: bool,
+    ) -> Result<OutputColumns, Box<dyn Error>> {
+        if columns.is_empty() {
+            return Ok(OutputColumns::Capped { keep_source });
+        }
+        let mut selected = Vec::new();
+        for column in columns {
+            let idx = resolve_column(header, column.trim())
+                .map_err(|e| Fb2SqliteError::Validation(format!("--columns: {}", e)))?;
+            if selected.contains(&idx) {
+                return Err(Fb2SqliteError::Validation(format!(
+                    "--columns: column \"{}\" is selected twice",
+                    header[idx]
+                ))
+                .into());
+            }
+            selected.push(idx);
+        }
+        Ok(OutputColumns::Selected(selected))
+    }
 
-    let db_handle = thread::spawn(move || -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut conn = Connection::open("firstbase.db")?;
-        let tx_db = conn.transaction()?;
+    /// Cut a header or data row to the output columns.
+    fn apply(&self, row: &mut Vec<String>) {
+        match self {
+            OutputColumns::Capped { keep_source } => cap_columns(row, *keep_source),
+            OutputColumns::Selected(selected) => {
+                *row = selected
+                    .iter()
+                    .map(|&idx| row.get_mut(idx).map(std::mem::take).unwrap_or_default())
+                    .collect();
+            }
+        }
+    }
 
-        if let Ok(headers) = rx.recv() {
-            let create_cols = headers
-                .iter()
-                .map(|h| {
-                    format!(
-                        "\"{}\" TEXT",
-                        h.replace(|c: char| !c.is_alphanumeric(), "_")
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join(", ");
-
-            tx_db.execute("DROP TABLE IF EXISTS data", [])?;
-            tx_db.execute(&format!("CREATE TABLE data ({})", create_cols), [])?;
-
-            let placeholders = vec!["?"; headers.len()].join(", ");
-            let query = format!("INSERT INTO data VALUES ({})", placeholders);
-
-            {
-                let mut stmt = tx_db.prepare(&query)?;
-                while let Ok(row) = rx.recv() {
-                    stmt.execute(rusqlite::params_from_iter(row))?;
-                }
+    /// Number of output columns of a record with `len` columns.
+    fn width(&self, len: usize) -> usize {
+        match self {
+            OutputColumns::Capped { .. } => len.min(MAX_COLUMNS),
+            OutputColumns::Selected(selected) => selected.len(),
+        }
+    }
+
+    /// Mark the output values of a raw CSV record in `pruner`.
+    fn observe(&self, pruner: &mut ColumnPruner, record: &StringRecord) {
+        match self {
+            OutputColumns::Capped { .. } => pruner.observe(record.iter()),
+            OutputColumns::Selected(selected) => {
+                pruner.observe(selected.iter().map(|&idx| record.get(idx).unwrap_or("")))
             }
         }
-        tx_db.commit()?;
-        Ok(())
-    });
+    }
+}
+
+/// Resolve `--product-desc-column` / `--match-columns` against the CSV
+/// header: a zero-based index, or a column name (exact, then case-insensitive).
+fn resolve_column(header: &[String], column: &str) -> Result<usize, Box<dyn Error>> {
+    if let Ok(idx) = column.parse::<usize>() {
+        if idx < header.len() {
+            return Ok(idx);
+        }
+        return Err(Fb2SqliteError::Validation(format!(
+            "Column index {} is out of range (CSV has {} columns)",
+            idx,
+            header.len()
+        ))
+        .into());
+    }
+    header
+        .iter()
+        .position(|h| h == column)
+        .or_else(|| header.iter().position(|h| h.eq_ignore_ascii_case(column)))
+        .ok_or_else(|| {
+            Fb2SqliteError::Validation(format!("Column \"{}\" not found in CSV header", column))
+                .into()
+        })
+}
+
+/// Columns of the product text used for matching.
+#[derive(Clone, Copy)]
+struct MatchColumns {
+    de: usize,
+    fr: usize,
+    it: usize,
+    brand: Option<usize>,
+}
+
+impl MatchColumns {
+    /// DE, FR and IT description and brand of a row (empty when missing).
+    fn texts(&self, row: &[String]) -> (String, String, String, String) {
+        let text = |col: Option<usize>| col.and_then(|c| row.get(c)).cloned().unwrap_or_default();
+        (
+            text(Some(self.de)),
+            text(Some(self.fr)),
+            text(Some(self.it)),
+            text(self.brand),
+        )
+    }
+}
+
+/// GS1 header names of the description and brand columns, looked up when the
+/// feed is too short for the usual positions 5 to 8.
+const GS1_MATCH_COLUMNS: [&str; 4] = [
+    "TradeItemDescription_DE",
+    "TradeItemDescription_FR",
+    "TradeItemDescription_IT",
+    "BrandName",
+];
+
+/// Pick the match columns from `--product-desc-column`, `--match-columns` or
+/// the GS1 layout, failing early when the header cannot provide them instead
+/// of matching empty text on every row.
+fn resolve_match_columns(
+    header: &[String],
+    args: &ColumnArgs,
+) -> Result<MatchColumns, Box<dyn Error>> {
+    if let Some(column) = &args.product_desc_column {
+        let col = resolve_column(header, column)?;
+        return Ok(MatchColumns {
+            de: col,
+            fr: col,
+            it: col,
+            brand: None,
+        });
+    }
+    if !args.match_columns.is_empty() {
+        if !(3..=4).contains(&args.match_columns.len()) {
+            return Err(Fb2SqliteError::Validation(
+                "--match-columns takes DE,FR,IT[,BRAND] (3 or 4 columns)".into(),
+            )
+            .into());
+        }
+        let cols = args
+            .match_columns
+            .iter()
+            .map(|c| resolve_column(header, c).map_err(|e| format!("--match-columns: {}", e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(MatchColumns {
+            de: cols[0],
+            fr: cols[1],
+            it: cols[2],
+            brand: cols.get(3).copied(),
+        });
+    }
+    // col 5 = TradeItemDescription_DE, 6 = FR, 7 = IT, 8 = BrandName
+    if header.len() > 8 {
+        return Ok(MatchColumns {
+            de: 5,
+            fr: 6,
+            it: 7,
+            brand: Some(8),
+        });
+    }
+    let by_name: Option<Vec<usize>> = GS1_MATCH_COLUMNS
+        .iter()
+        .map(|name| resolve_column(header, name).ok())
+        .collect();
+    match by_name {
+        Some(cols) => {
+            println!(
+                "Feed has only {} columns, matching the GS1 description columns by name",
+                header.len()
+            );
+            Ok(MatchColumns {
+                de: cols[0],
+                fr: cols[1],
+                it: cols[2],
+                brand: Some(cols[3]),
+            })
+        }
+        None => Err(Fb2SqliteError::Validation(format!(
+            "CSV has only {} columns, but matching reads the GS1 descriptions at positions 5-8 \
+             (DE, FR, IT, brand); select them with --match-columns DE,FR,IT[,BRAND] \
+             or --product-desc-column",
+            header.len()
+        ))
+        .into()),
+    }
+}
+
+/// The `--since` filter with its date column resolved against `header`.
+fn resolve_date_filter(
+    args: &SinceArgs,
+    header: &[String],
+) -> Result<Option<DateFilter>, Box<dyn Error>> {
+    let (Some(since), Some(column)) = (args.since, &args.date_column) else {
+        return Ok(None);
+    };
+    Ok(Some(DateFilter {
+        column: resolve_column(header, column)?,
+        since,
+        include_undated: args.include_undated,
+    }))
+}
+
+/// Error for a feed without a single record.
+fn no_rows() -> Fb2SqliteError {
+    Fb2SqliteError::Parse("CSV has no rows".into())
+}
+
+/// Apply the malformed-record policy: fail fast by default, or with
+/// `--skip-bad-rows` warn, count the record and return `None`.
+fn check_record(
+    result: csv::Result<StringRecord>,
+    skip_bad_rows: bool,
+    skipped: &mut usize,
+) -> Result<Option<StringRecord>, Box<dyn Error>> {
+    match result {
+        Ok(record) => Ok(Some(record)),
+        Err(e) if skip_bad_rows => {
+            eprintln!("Warning: skipping malformed CSV record: {}", e);
+            *skipped += 1;
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `--prune-empty-columns` for `import`: a first pass over the CSV finds the
+/// columns (within `MAX_COLUMNS`) that are empty in every data row. Malformed
+/// records are ignored here; the main pass skips them or fails on them.
+fn scan_empty_columns(
+    csv_content: &str,
+    has_header: bool,
+    date_filter: Option<&DateFilter>,
+    output_columns: &OutputColumns,
+) -> ColumnPruner {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(Cursor::new(csv_content));
+    let mut records = reader.records().filter_map(Result::ok).peekable();
+    let width = records.peek().map_or(0, |r| output_columns.width(r.len()));
+    if has_header {
+        records.next();
+    }
+    let mut pruner = ColumnPruner::new(width);
+    for record in records {
+        if date_filter.is_none_or(|filter| filter.keeps(record.iter())) {
+            output_columns.observe(&mut pruner, &record);
+        }
+    }
+    pruner
+}
+
+/// Column names `col1`, `col2`, ... for a feed without a header row (`--no-header`).
+fn synthetic_header(width: usize) -> Vec<String> {
+    (1..=width).map(|i| format!("col{}", i)).collect()
+}
+
+/// The header row of the CSV (`col1`..`colN` with `--no-header`).
+fn csv_header(csv_content: &str, no_header: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(Cursor::new(csv_content));
+    let first = reader.records().next().ok_or_else(no_rows)??;
+    Ok(if no_header {
+        synthetic_header(first.len())
+    } else {
+        first.iter().map(|s| s.to_string()).collect()
+    })
+}
+
+/// `--fts`: the header names of the DE, FR and IT description columns, for
+/// `db::spawn_db_writer` (a column given several times is indexed once).
+fn fts_columns(header: &[String], columns: &MatchColumns) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for col in [columns.de, columns.fr, columns.it] {
+        if let Some(name) = header.get(col).filter(|name| !names.contains(name)) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+fn run_normal(
+    csv_content: &str,
+    args: &ImportArgs,
+    work_dir: &Path,
+    stdout: Option<StdoutSink>,
+) -> Result<(), Box<dyn Error>> {
+    let db_path = args.db.output_path(work_path(work_dir, "firstbase.db"));
+    let db_filename = db_path.as_str();
+    let build_path = build_path(db_filename, &args.db)?;
+    let mut report = Report::new(args.report_format);
+    let renames = args.db.column_renames()?;
+
+    let (tx, rx) = mpsc::channel::<Vec<String>>();
+    let archive = start_archive(&args.archive, &renames);
+
+    // Import has no --match-columns: the GS1 description columns are indexed
+    let fts_columns = if args.db.fts {
+        let header = csv_header(csv_content, args.no_header)?;
+        fts_columns(&header, &resolve_match_columns(&header, &ColumnArgs::default())?)
+    } else {
+        Vec::new()
+    };
+    let db_handle = spawn_output_writer(build_path.clone(), rx, &args.db, &renames, fts_columns);
 
     let mut reader = ReaderBuilder::new()
         .has_headers(false)
         .from_reader(Cursor::new(csv_content));
 
+    // Set from the header row, whose names locate the identifier columns
+    let mut computed = Vec::new();
+    // The date column is resolved before the pruning pre-pass, which
+    // only looks at the rows the filter keeps
+    let date_filter = if args.since.since.is_some() {
+        resolve_date_filter(&args.since, &csv_header(csv_content, args.no_header)?)?
+    } else {
+        None
+    };
+    let output_columns = if args.db.columns.is_empty() {
+        OutputColumns::Capped {
+            keep_source: args.input.merged(),
+        }
+    } else {
+        let header = csv_header(csv_content, args.no_header)?;
+        OutputColumns::new(&header, &args.db.columns, args.input.merged())?
+    };
+    let mut date_counts = DateCounts::default();
+    let pruner = args.db.prune_empty_columns.then(|| {
+        scan_empty_columns(
+            csv_content,
+            !args.no_header,
+            date_filter.as_ref(),
+            &output_columns,
+        )
+    });
     let mut line_count = 0;
+    let mut skipped = 0;
+    let mut header_sent = false;
+
+    let send = |mut row: Vec<String>| -> Result<(), Box<dyn Error>> {
+        // The archive keeps its full schema across runs
+        if let Some((_, archive_tx, _)) = &archive {
+            archive_tx.send(row.clone())?;
+        }
+        if let Some(pruner) = &pruner {
+            pruner.apply(&mut row);
+        }
+        tx.send(row)?;
+        Ok(())
+    };
 
     for result in reader.records() {
-        let record = result?;
-        let row_data: Vec<String> = record.iter().take(15).map(|s| s.to_string()).collect();
-        tx.send(row_data)?;
+        if interrupt::interrupted() {
+            // The writer threads roll back when they see the flag
+            break;
+        }
+        let Some(record) = check_record(result, args.input.skip_bad_rows, &mut skipped)? else {
+            continue;
+        };
+        let is_header = !header_sent && !args.no_header;
+        if let Some(filter) = date_filter.as_ref().filter(|_| !is_header) {
+            if !filter.count(record.iter(), &mut date_counts) {
+                continue;
+            }
+        }
+        let mut row_data: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        output_columns.apply(&mut row_data);
+        if !header_sent {
+            header_sent = true;
+            // Without a header row the first record is data like the rest
+            let mut header = if args.no_header {
+                let mut header = synthetic_header(record.len());
+                output_columns.apply(&mut header);
+                header
+            } else {
+                std::mem::take(&mut row_data)
+            };
+            computed = computed_columns(&args.db.computed_kinds(), &header);
+            append_computed_headers(&mut header, &computed);
+            if let Some(pruner) = &pruner {
+                pruner.report(&header);
+            }
+            send(header)?;
+            if !args.no_header {
+                line_count += 1;
+                continue;
+            }
+        }
+        append_computed(&mut row_data, &computed);
+        send(row_data)?;
         line_count += 1;
     }
 
     drop(tx);
 
-    db_handle
-        .join()
-        .map_err(|_| "The database thread panicked")?
-        .map_err(|e| e.to_string())?;
+    // Join both writers before reporting an error, so both have rolled back
+    let inserted = join_db_writer(db_handle, db_filename);
+    finish_archive(archive)?;
+    let inserted = inserted?;
+    finish_db(&build_path, &args.db)?;
+    publish_db(&build_path, db_filename)?;
+
+    report.line(format!(
+        "Database {} created successfully with {} rows.",
+        db_filename, inserted
+    ));
+    report.line(format!("Total CSV lines processed: {}", line_count));
+    report.set("database", db_filename);
+    report.set("rows", inserted);
+    report.set("csv_lines", line_count);
+    if let Some(filter) = &date_filter {
+        filter.report(&date_counts, &mut report);
+    }
+    if args.input.skip_bad_rows {
+        report.line(format!("Skipped malformed CSV records: {}", skipped));
+        report.set("skipped_records", skipped);
+    }
 
-    println!("Database {} created successfully.", db_filename);
-    println!("Total CSV lines processed: {}", line_count);
+    if let Some(stdout) = report.finish(stdout)? {
+        let bytes = stdout.stream_file(db_filename)?;
+        println!("Wrote {} bytes to stdout", bytes);
+        return Ok(());
+    }
 
     // SCP Transfer
-    let remote_dest = "zdavatz@65.109.137.20:/var/www/pillbox.oddb.org/";
-    println!("Transferring {} to {}...", db_filename, remote_dest);
+    interrupt::check()?;
+    let upload_options = args.upload.deploy_options(&args.db);
+    upload_db(&[db_filename], &args.upload.destination(), &upload_options)?;
 
-    let status = Command::new("scp")
-        .arg(db_filename)
-        .arg(remote_dest)
-        .status()?;
+    Ok(())
+}
 
-    if status.success() {
-        println!("SCP transfer complete.");
-    } else {
-        return Err(format!("SCP failed with exit code: {:?}", status.code()).into());
+/// A product row with its MiGeL columns appended, plus the match outcome.
+struct MatchedRow<'a> {
+    row: Vec<String>,
+    /// The matched MiGeL item and its score
+    migel: Option<(&'a MigelItem, f64)>,
+    /// DE description text the row was matched with (FR or IT if it is
+    /// empty), on one line (for `--sample`, `--review`, `--list-matches-csv`)
+    description: String,
+    /// With `--multi-match`: all matches within the score band (best first)
+    alternatives: Vec<(String, f64)>,
+    /// Why the row was not scored at all; counted apart from matcher misses
+    skip_reason: Option<SkipReason>,
+    /// Keywords of the matched item found in the product text
+    matched_keywords: Vec<String>,
+    /// With `--audit`: the scored DE/FR/IT description and brand texts
+    product_text: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SkipReason {
+    /// No description text in any language (nor a brand)
+    NoDescription,
+    /// Fewer keyword tokens than `--min-product-tokens`
+    TooFewTokens,
+    /// The text contains a `--blocklist` word
+    Blocked,
+}
+
+/// Matching context shared by all product rows of a `migel` run.
+struct RowMatcher<'a, 'o> {
+    migel_items: &'a [MigelItem],
+    keyword_index: &'a KeywordIndex,
+    options: &'a MatchOptions,
+    /// Product text columns (`--match-columns`, `--product-desc-column`)
+    columns: MatchColumns,
+    /// `--explain`: append a column with the matched keywords
+    explain: bool,
+    /// The CSV columns written before the MiGeL columns (`--columns`)
+    output_columns: OutputColumns,
+    /// `--multi-match`: score band below the best match and maximum number of matches
+    multi_match: Option<(f64, usize)>,
+    /// `--max-limitation-length`: maximum characters of `migel_limitation`
+    max_limitation_length: Option<usize>,
+    /// `--overrides`: MiGeL items rejected for a product are skipped
+    overrides: Option<&'o Overrides>,
+    /// `--blocklist`: products with a blocked word are not matched
+    blocklist: Option<&'o Blocklist>,
+    /// `--migel-file`: append a column with the edition of the matched item
+    edition: bool,
+    /// `--audit`: keep the scored product text for the `match_audit` table
+    audit: bool,
+}
+
+/// Shorten `text` to at most `max_chars` characters including a trailing
+/// ellipsis. The cut goes to the last word boundary unless that lies in the
+/// first half of the kept text.
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let keep: String = text.chars().take(max_chars - 1).collect();
+    let cut = match keep.rfind(char::is_whitespace) {
+        Some(pos) if pos >= keep.len() / 2 => &keep[..pos],
+        _ => keep.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+impl<'a> RowMatcher<'a, '_> {
+    /// Match a single product row against the MiGeL index. The row is cut to
+    /// the `output_columns` before the MiGeL columns are appended; with `explain`, a
+    /// fifth column with the matched keywords follows, with `edition` the
+    /// edition column comes last.
+    fn match_row(&self, row_data: Vec<String>) -> MatchedRow<'a> {
+        let (desc_de, desc_fr, desc_it, brand) = self.columns.texts(&row_data);
+
+        let min_tokens = self.options.min_product_tokens;
+        let skip_reason = if [&desc_de, &desc_fr, &desc_it, &brand]
+            .iter()
+            .all(|text| text.trim().is_empty())
+        {
+            Some(SkipReason::NoDescription)
+        } else if self
+            .blocklist
+            .is_some_and(|b| b.blocks(&[&desc_de, &desc_fr, &desc_it, &brand]))
+        {
+            Some(SkipReason::Blocked)
+        } else if min_tokens > 1
+            && product_token_count(&desc_de, &desc_fr, &desc_it, &brand) < min_tokens
+        {
+            Some(SkipReason::TooFewTokens)
+        } else {
+            None
+        };
+        let description = [&desc_de, &desc_fr, &desc_it]
+            .into_iter()
+            .find(|text| !text.trim().is_empty())
+            .map_or(String::new(), |text| single_line(text));
+        let product_text = if self.audit {
+            let texts: Vec<&str> = [&desc_de, &desc_fr, &desc_it, &brand]
+                .iter()
+                .map(|t| t.trim())
+                .filter(|t| !t.is_empty())
+                .collect();
+            texts.join(" | ")
+        } else {
+            String::new()
+        };
+        let mut row_with_migel = row_data;
+        self.output_columns.apply(&mut row_with_migel);
+
+        let matches = if skip_reason.is_some() {
+            Vec::new()
+        } else {
+            let limit = self.multi_match.map_or(1, |(_, max_matches)| max_matches);
+            // Rank further candidates to take the place of rejected items
+            let gtin = row_with_migel.first().map_or("", String::as_str);
+            let rejected = self.overrides.map_or(0, |o| o.rejected_count(gtin));
+            let mut matches = rank_migel_matches(
+                &desc_de,
+                &desc_fr,
+                &desc_it,
+                &brand,
+                self.migel_items,
+                self.keyword_index,
+                self.options,
+                limit + rejected,
+            );
+            if let Some(overrides) = self.overrides.filter(|_| rejected > 0) {
+                matches.retain(|m| {
+                    overrides.decision(gtin, &m.item.position_nr) != Some(Decision::Reject)
+                });
+                matches.truncate(limit);
+            }
+            matches
+        };
+
+        if let Some(migel) = matches.first() {
+            row_with_migel.push(migel.item.position_nr.clone());
+            row_with_migel.push(migel.item.bezeichnung.clone());
+            row_with_migel.push(match self.max_limitation_length {
+                Some(max) => truncate_text(&migel.item.limitation, max),
+                None => migel.item.limitation.clone(),
+            });
+            row_with_migel.push(migel.item.betrag.map(|b| b.to_string()).unwrap_or_default());
+            if self.explain {
+                row_with_migel.push(migel.matched_keywords.join(" "));
+            }
+            if self.edition {
+                row_with_migel.push(migel.item.edition.clone().unwrap_or_default());
+            }
+            let alternatives = match self.multi_match {
+                Some((band, _)) => matches
+                    .iter()
+                    .filter(|m| m.score >= migel.score - band)
+                    .map(|m| (m.item.position_nr.clone(), m.score))
+                    .collect(),
+                None => Vec::new(),
+            };
+            MatchedRow {
+                row: row_with_migel,
+                migel: Some((migel.item, migel.score)),
+                description,
+                alternatives,
+                skip_reason,
+                matched_keywords: migel.matched_keywords.clone(),
+                product_text,
+            }
+        } else {
+            row_with_migel.push(String::new());
+            row_with_migel.push(String::new());
+            row_with_migel.push(String::new());
+            row_with_migel.push(String::new());
+            if self.explain {
+                row_with_migel.push(String::new());
+            }
+            if self.edition {
+                row_with_migel.push(String::new());
+            }
+            MatchedRow {
+                row: row_with_migel,
+                migel: None,
+                description,
+                alternatives: Vec::new(),
+                skip_reason,
+                matched_keywords: Vec::new(),
+                product_text,
+            }
+        }
     }
+}
 
+/// `--audit`: the matching decision for every product row, matched or not.
+fn audit_entries(results: &[MatchedRow]) -> Vec<AuditEntry> {
+    results
+        .iter()
+        .map(|result| {
+            let decision = match (result.migel, result.skip_reason) {
+                (Some(_), _) => "matched",
+                (None, Some(SkipReason::NoDescription)) => "no_description",
+                (None, Some(SkipReason::TooFewTokens)) => "too_few_tokens",
+                (None, Some(SkipReason::Blocked)) => "blocked",
+                (None, None) => "no_match",
+            };
+            AuditEntry {
+                gtin: result.row.first().cloned().unwrap_or_default(),
+                product_text: result.product_text.clone(),
+                position_nr: result.migel.map(|(item, _)| item.position_nr.clone()),
+                score: result.migel.map(|(_, score)| score),
+                matched_keywords: result.matched_keywords.join(" "),
+                decision,
+            }
+        })
+        .collect()
+}
+
+/// `--list-matches-csv`: every product row with its MiGeL assignment, empty
+/// for unmatched products, as a CSV for reviewers working in a spreadsheet.
+fn write_matches_csv(path: &str, results: &[MatchedRow]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record([
+        "gtin",
+        "product_description",
+        "migel_code",
+        "migel_bezeichnung",
+        "score",
+    ])?;
+    for result in results {
+        let gtin = result.row.first().map_or("", String::as_str);
+        let (code, bezeichnung, score) = match result.migel {
+            Some((item, score)) => (
+                item.position_nr.as_str(),
+                item.bezeichnung.as_str(),
+                format!("{:.2}", score),
+            ),
+            None => ("", "", String::new()),
+        };
+        writer.write_record([gtin, &result.description, code, bezeichnung, &score])?;
+    }
+    writer.flush()?;
+    println!("Wrote {} products to {}", results.len(), path);
     Ok(())
 }
 
-/// Match a single product row against the MiGeL index.
-/// Returns (row_with_migel_columns, matched).
-fn match_product_row(
-    row_data: Vec<String>,
-    migel_items: &[MigelItem],
-    keyword_index: &HashMap<String, Vec<usize>>,
-) -> (Vec<String>, bool) {
-    // col 5 = TradeItemDescription_DE, 6 = FR, 7 = IT, 8 = BrandName
-    let desc_de = row_data.get(5).cloned().unwrap_or_default();
-    let desc_fr = row_data.get(6).cloned().unwrap_or_default();
-    let desc_it = row_data.get(7).cloned().unwrap_or_default();
-    let brand = row_data.get(8).cloned().unwrap_or_default();
-
-    let mut row_with_migel = row_data;
-
-    if let Some(migel) =
-        find_best_migel_match(&desc_de, &desc_fr, &desc_it, &brand, migel_items, keyword_index)
-    {
-        row_with_migel.push(migel.position_nr.clone());
-        row_with_migel.push(migel.bezeichnung.clone());
-        row_with_migel.push(migel.limitation.clone());
-        (row_with_migel, true)
-    } else {
-        row_with_migel.push(String::new());
-        row_with_migel.push(String::new());
-        row_with_migel.push(String::new());
-        (row_with_migel, false)
+/// Number of `--score-histogram` buckets, of equal width over scores 0 to 1.
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// Widest `#` bar of the `--score-histogram` table.
+const HISTOGRAM_BAR: usize = 40;
+
+/// `--score-histogram`: number of matched rows per score bucket of width
+/// 1 / `HISTOGRAM_BUCKETS`; a score of 1 falls into the last bucket.
+fn score_histogram(results: &[MatchedRow]) -> [usize; HISTOGRAM_BUCKETS] {
+    let mut buckets = [0; HISTOGRAM_BUCKETS];
+    for (_, score) in results.iter().filter_map(|r| r.migel) {
+        // The epsilon keeps 0.3 in 0.3-0.4 despite 0.3 * 10 = 2.999...
+        let bucket = (score * HISTOGRAM_BUCKETS as f64 + 1e-9).floor() as usize;
+        buckets[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+    buckets
+}
+
+/// `--match-distribution`: per matched MiGeL item the number of products it
+/// got and their average score, the items with most products first (ties by
+/// position number), so over-broad keyword sets stand out.
+fn match_distribution<'a>(results: &[MatchedRow<'a>]) -> Vec<(&'a MigelItem, usize, f64)> {
+    let mut items: BTreeMap<&str, (&MigelItem, usize, f64)> = BTreeMap::new();
+    for (item, score) in results.iter().filter_map(|r| r.migel) {
+        let entry = items.entry(&item.position_nr).or_insert((item, 0, 0.0));
+        entry.1 += 1;
+        entry.2 += score;
     }
+    let mut distribution: Vec<(&MigelItem, usize, f64)> = items
+        .into_values()
+        .map(|(item, count, total)| (item, count, total / count as f64))
+        .collect();
+    // BTreeMap order and a stable sort keep equal counts by position number
+    distribution.sort_by_key(|&(_, products, _)| Reverse(products));
+    distribution
 }
 
-fn run_migel(csv_content: &str, deploy: bool) -> Result<(), Box<dyn Error>> {
-    let migel_url = "https://www.bag.admin.ch/dam/de/sd-web/77j5rwUTzbkq/Mittel-%20und%20Gegenst%C3%A4ndeliste%20per%2001.01.2026%20in%20Excel-Format.xlsx";
-    let migel_file = "migel.xlsx";
+/// `--sample`: up to `n` matched rows picked with a seeded RNG (reproducible
+/// for the same input and seed), formatted for review.
+fn sample_matches(results: &[MatchedRow], n: usize, seed: u64) -> Vec<String> {
+    let matched: Vec<&MatchedRow> = results.iter().filter(|r| r.migel.is_some()).collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    matched
+        .choose_multiple(&mut rng, n)
+        .filter_map(|result| {
+            let (item, score) = result.migel?;
+            Some(format!(
+                "  {}  \"{}\"  ->  {} {}  (score {:.2})",
+                result.row.first().map_or("", String::as_str),
+                result.description,
+                item.position_nr,
+                item.bezeichnung,
+                score
+            ))
+        })
+        .collect()
+}
 
-    // 1. Download MiGeL XLSX
-    println!("Downloading MiGeL XLSX...");
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("fb2sqlite/0.1")
-        .build()?;
-    let response = client.get(migel_url).send()?;
-    if !response.status().is_success() {
-        return Err(format!(
-            "Failed to download MiGeL XLSX: HTTP {}",
-            response.status()
-        )
-        .into());
+/// `--review`: ask about every match scoring below `below` that has no
+/// decision in the overrides file yet. Decisions are appended to `path`
+/// right away; rejected matches are dropped from this run (later runs take
+/// the next candidate).
+fn review_matches(
+    results: &mut [MatchedRow],
+    overrides: &mut Overrides,
+    path: &str,
+    below: f64,
+) -> Result<(), Box<dyn Error>> {
+    if !io::stdin().is_terminal() {
+        println!("Warning: --review needs an interactive terminal, no matches reviewed");
+        return Ok(());
     }
+    let gtin = |result: &MatchedRow| result.row.first().cloned().unwrap_or_default();
+    let pending: Vec<usize> = (0..results.len())
+        .filter(|&i| {
+            results[i].migel.is_some_and(|(item, score)| {
+                score < below && overrides.decision(&gtin(&results[i]), &item.position_nr).is_none()
+            })
+        })
+        .collect();
+    println!("Reviewing {} matches scoring below {:.2}", pending.len(), below);
+    let (mut accepted, mut rejected) = (0, 0);
+    let mut line = String::new();
+    for (n, &idx) in pending.iter().enumerate() {
+        let result = &mut results[idx];
+        let Some((item, score)) = result.migel else {
+            continue;
+        };
+        let code = gtin(result);
+        println!("[{}/{}] {}  \"{}\"", n + 1, pending.len(), code, result.description);
+        println!("  -> {} {}  (score {:.2})", item.position_nr, item.bezeichnung, score);
+        let answer = loop {
+            print!("  Accept? [y]es / [n]o / [s]kip / [q]uit: ");
+            io::stdout().flush()?;
+            line.clear();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                break "q".to_string();
+            }
+            let answer = line.trim().to_lowercase();
+            if ["y", "n", "s", "q"].contains(&answer.as_str()) {
+                break answer;
+            }
+        };
+        let decision = match answer.as_str() {
+            "y" => Decision::Accept,
+            "n" => Decision::Reject,
+            "s" => continue,
+            _ => break,
+        };
+        overrides.record(path, &code, &item.position_nr, decision)?;
+        if decision == Decision::Reject {
+            result.migel = None;
+            result.alternatives.clear();
+            result.matched_keywords.clear();
+            rejected += 1;
+        } else {
+            accepted += 1;
+        }
+    }
+    println!("Review: {} accepted, {} rejected (saved to {})", accepted, rejected, path);
+    Ok(())
+}
+
+/// MiGeL category of a position number: its first segment (e.g. "15" of "15.10.01.00.1").
+fn migel_category(position_nr: &str) -> &str {
+    position_nr.split('.').next().unwrap_or(position_nr)
+}
+
+const MIGEL_FILE: &str = "migel.xlsx";
+
+/// MiGeL XLSX published by BAG (`download_migel`, checked by `doctor`).
+const MIGEL_URL: &str = "https://www.bag.admin.ch/dam/de/sd-web/77j5rwUTzbkq/Mittel-%20und%20Gegenst%C3%A4ndeliste%20per%2001.01.2026%20in%20Excel-Format.xlsx";
+
+/// Path of a downloaded input or output file inside `--work-dir` (the
+/// current directory by default, where the name is used unchanged).
+fn work_path(work_dir: &Path, name: &str) -> String {
+    work_dir.join(name).to_string_lossy().into_owned()
+}
+
+/// `--skip-unchanged`: SHA-256 of the last imported feed, in the work directory.
+const FEED_HASH_FILE: &str = ".last_feed_hash";
+
+/// SHA-256 of the feed text, or `None` if it equals the hash stored in
+/// `hash_file` by the last import.
+fn changed_feed_hash(content: &str, hash_file: &str) -> Option<String> {
+    let hash = sha256_hex(content.as_bytes());
+    let last = fs::read_to_string(hash_file).unwrap_or_default();
+    (last.trim() != hash).then_some(hash)
+}
+
+/// Download the MiGeL XLSX from BAG to `MIGEL_FILE` in the work directory
+/// and return its path.
+fn download_migel(work_dir: &Path, client: &Client) -> Result<String, Box<dyn Error>> {
+    let migel_file = work_path(work_dir, MIGEL_FILE);
+    println!("Downloading MiGeL XLSX...");
+    let response = send_checked(client.get(MIGEL_URL), "MiGeL XLSX")?;
     let bytes = response.bytes()?;
-    fs::write(migel_file, &bytes)?;
+    fs::write(&migel_file, &bytes)?;
     println!("MiGeL XLSX saved ({} bytes)", bytes.len());
+    Ok(migel_file)
+}
+
+/// The MiGeL workbooks to parse with their edition labels, oldest first: the
+/// `--migel-file` workbooks, or else the BAG download without a label.
+fn migel_files(
+    matching: &MatchArgs,
+    work_dir: &Path,
+    client: &Client,
+) -> Result<MigelFiles, Box<dyn Error>> {
+    if matching.migel_file.is_empty() {
+        return Ok(vec![(None, download_migel(work_dir, client)?)]);
+    }
+    let mut editions: Vec<&str> = Vec::new();
+    for file in &matching.migel_file {
+        if editions.contains(&file.edition.as_str()) {
+            return Err(Fb2SqliteError::Validation(format!(
+                "--migel-file edition \"{}\" is given twice",
+                file.edition
+            ))
+            .into());
+        }
+        editions.push(&file.edition);
+    }
+    Ok(matching
+        .migel_file
+        .iter()
+        .map(|file| (Some(file.edition.clone()), file.path.clone()))
+        .collect())
+}
+
+/// Write the keyword index (keyword → position numbers) for other tools:
+/// JSON object for `.json` paths, otherwise CSV with one row per posting.
+fn export_keywords(
+    path: &str,
+    migel_items: &[MigelItem],
+    keyword_index: &KeywordIndex,
+) -> Result<(), Box<dyn Error>> {
+    let postings = keyword_postings(migel_items, keyword_index);
+    if path.ends_with(".json") {
+        let file = BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, &postings)?;
+    } else {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["keyword", "position_nr"])?;
+        for (keyword, positions) in &postings {
+            for position in positions {
+                writer.write_record([keyword, position])?;
+            }
+        }
+        writer.flush()?;
+    }
+    println!("Exported {} keywords to {}", postings.len(), path);
+    Ok(())
+}
+
+/// Download and parse the MiGeL XLSX (steps 1–2 of `migel`), report items that
+/// need attention, and build the keyword index. Several `--migel-file`
+/// editions are merged into one item list first.
+fn load_migel(
+    matching: &MatchArgs,
+    options: &MatchOptions,
+    work_dir: &Path,
+    client: &Client,
+) -> Result<MigelData, Box<dyn Error>> {
+    // 1. Download MiGeL XLSX (not needed with --migel-csv or --seed-migel-items)
+    let migel_files = if matching.migel_csv.is_some() || matching.seed_migel_items.is_some() {
+        Vec::new()
+    } else {
+        migel_files(matching, work_dir, client)?
+    };
 
     // 2. Parse MiGeL items
     println!("Parsing MiGeL items...");
-    let migel_items = parse_migel_items(migel_file)?;
+    let supplements = match &matching.migel_keywords {
+        Some(path) => load_keyword_supplements(path)?,
+        None => HashMap::new(),
+    };
+    let parse_options = ParseOptions {
+        languages: matching.sheet_languages.clone(),
+        supplements,
+        full_description: matching.full_description_keywords,
+        max_keywords: matching.max_keywords_per_item.map(usize::from),
+        category_keywords: !matching.no_category_keywords,
+        abbreviations: options.abbreviations.clone(),
+    };
+    let mut editions = Vec::new();
+    if let Some(path) = &matching.migel_csv {
+        let items = parse_migel_csv(path, &parse_options)?;
+        println!("Read {} MiGeL items from {}", items.len(), path);
+        editions.push(items);
+    }
+    if let Some(path) = &matching.seed_migel_items {
+        let items = parse_migel_seed(path, &parse_options)?;
+        println!("Read {} seeded MiGeL items from {}", items.len(), path);
+        editions.push(items);
+    }
+    for (edition, path) in &migel_files {
+        let mut items = parse_migel_items(path, &parse_options)?;
+        if let Some(edition) = edition {
+            println!("MiGeL edition {}: {} items from {}", edition, items.len(), path);
+            for item in &mut items {
+                item.edition = Some(edition.clone());
+            }
+        }
+        editions.push(items);
+    }
+    let edition_count = editions.len();
+    let migel_items = merge_editions(editions);
+    if edition_count > 1 {
+        let newest = migel_files.last().and_then(|(edition, _)| edition.as_ref());
+        let older = migel_items
+            .iter()
+            .filter(|i| i.edition.as_ref() != newest)
+            .count();
+        println!(
+            "Merged {} MiGeL editions, {} items only in older editions",
+            edition_count, older
+        );
+    }
+    if !parse_options.supplements.is_empty() {
+        let unknown: Vec<&str> = parse_options
+            .supplements
+            .keys()
+            .filter(|pos| !migel_items.iter().any(|i| &i.position_nr == *pos))
+            .map(|pos| pos.as_str())
+            .collect();
+        println!(
+            "Merged manual keywords for {} MiGeL positions",
+            parse_options.supplements.len() - unknown.len()
+        );
+        if !unknown.is_empty() {
+            eprintln!(
+                "Warning: --migel-keywords lists unknown position numbers: {}",
+                unknown.join(", ")
+            );
+        }
+    }
     println!(
         "Found {} MiGeL items with position numbers",
         migel_items.len()
     );
+    let fallback_count = migel_items.iter().filter(|i| i.category_fallback).count();
+    if fallback_count > 0 {
+        println!(
+            "{} MiGeL items have no own keywords, using their category description",
+            fallback_count
+        );
+    }
+    if let Some(max) = matching.max_keywords_per_item {
+        let trimmed = migel_items.iter().filter(|i| i.keywords_trimmed).count();
+        println!(
+            "Trimmed the keywords of {} MiGeL items to at most {} per language",
+            trimmed, max
+        );
+    }
+    let unmatchable = items_without_keywords(&migel_items);
+    if !unmatchable.is_empty() {
+        let positions: Vec<&str> = unmatchable.iter().map(|i| i.position_nr.as_str()).collect();
+        eprintln!(
+            "Warning: {} MiGeL items have no keywords and can never match: {}",
+            positions.len(),
+            positions.join(", ")
+        );
+    }
 
     let keyword_index = build_keyword_index(&migel_items);
     println!(
         "Built keyword index with {} unique keywords",
         keyword_index.len()
     );
+    if let Some(path) = &matching.export_keywords {
+        export_keywords(path, &migel_items, &keyword_index)?;
+    }
+
+    Ok((migel_items, keyword_index))
+}
+
+fn run_migel(
+    csv_content: &str,
+    args: &MigelArgs,
+    work_dir: &Path,
+    client: &Client,
+    stdout: Option<StdoutSink>,
+) -> Result<(), Box<dyn Error>> {
+    let options = args.matching.options()?;
+    let renames = args.db.column_renames()?;
+    let blocklist = args.blocklist.as_deref().map(Blocklist::load).transpose()?;
+    let (migel_items, keyword_index) = load_migel(&args.matching, &options, work_dir, client)?;
+    let mut report = Report::new(args.report_format);
 
     // 3. Generate output filename
-    let db_filename = if deploy {
+    let db_name = if args.deploy {
         "firstbase_migel.db".to_string()
     } else {
         let now = Local::now();
         now.format("firstbase_migel_%d.%m.%Y.db").to_string()
     };
+    let db_filename = args.db.output_path(work_path(work_dir, &db_name));
 
     // 4. Parse CSV — collect all rows first for parallel processing
     println!("Reading CSV rows...");
@@ -190,142 +1133,1027 @@ fn run_migel(csv_content: &str, deploy: bool) -> Result<(), Box<dyn Error>> {
 
     let mut headers: Option<Vec<String>> = None;
     let mut data_rows: Vec<Vec<String>> = Vec::new();
+    let mut match_columns: Option<MatchColumns> = None;
+    let mut output_columns: Option<OutputColumns> = None;
+    let mut fts_names = Vec::new();
+    let mut input_columns = 0;
+    let mut computed = Vec::new();
+    let mut skipped = 0;
+    let mut date_filter = None;
+    let mut date_counts = DateCounts::default();
 
     for result in reader.records() {
-        let record = result?;
-        let row_data: Vec<String> = record.iter().take(15).map(|s| s.to_string()).collect();
+        interrupt::check()?;
+        let Some(record) = check_record(result, args.input.skip_bad_rows, &mut skipped)? else {
+            continue;
+        };
+        // Rows are kept whole until matched: the description column may lie
+        // beyond the MAX_COLUMNS cap of the output table
+        let row_data: Vec<String> = record.iter().map(|s| s.to_string()).collect();
 
         if headers.is_none() {
             // First row is the header
+            let columns = resolve_match_columns(&row_data, &args.columns)?;
+            if args.db.fts {
+                fts_names = fts_columns(&row_data, &columns);
+                fts_names.push("migel_bezeichnung".to_string());
+            }
+            match_columns = Some(columns);
+            date_filter = resolve_date_filter(&args.since, &row_data)?;
+            let columns = OutputColumns::new(&row_data, &args.db.columns, args.input.merged())?;
             let mut h = row_data;
+            columns.apply(&mut h);
+            output_columns = Some(columns);
+            input_columns = h.len();
+            computed = computed_columns(&args.db.computed_kinds(), &h);
             h.push("migel_code".to_string());
             h.push("migel_bezeichnung".to_string());
             h.push("migel_limitation".to_string());
+            h.push("migel_betrag".to_string());
+            if args.explain {
+                h.push("migel_matched_keywords".to_string());
+            }
+            if !args.matching.migel_file.is_empty() {
+                h.push("migel_edition".to_string());
+            }
+            append_computed_headers(&mut h, &computed);
             headers = Some(h);
-        } else {
+        } else if date_filter
+            .as_ref()
+            .is_none_or(|filter| filter.count(record.iter(), &mut date_counts))
+        {
             data_rows.push(row_data);
         }
     }
+    if let Some(filter) = &date_filter {
+        filter.report(&date_counts, &mut report);
+    }
 
-    let headers = headers.ok_or("CSV has no rows")?;
+    let mut headers = headers.ok_or_else(no_rows)?;
     let total_rows = data_rows.len();
     println!("Collected {} data rows, matching in parallel...", total_rows);
 
     // 5. Match products to MiGeL items IN PARALLEL using rayon
-    let results: Vec<(Vec<String>, bool)> = data_rows
+    // Threads only split the matching; rows keep their CSV order because the
+    // indexed collect below preserves it, and SQLite writes stay on one thread.
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.into())
+            .build_global()?;
+        println!("Matching with {} threads", threads);
+    }
+    let mut overrides = args.overrides.as_deref().map(Overrides::load).transpose()?;
+    let matcher = RowMatcher {
+        migel_items: &migel_items,
+        keyword_index: &keyword_index,
+        options: &options,
+        columns: match_columns.ok_or_else(no_rows)?,
+        explain: args.explain,
+        output_columns: output_columns.ok_or_else(no_rows)?,
+        multi_match: args
+            .multi_match
+            .then_some((args.match_band, args.max_matches)),
+        max_limitation_length: args.max_limitation_length.map(usize::from),
+        overrides: overrides.as_ref(),
+        blocklist: blocklist.as_ref(),
+        edition: !args.matching.migel_file.is_empty(),
+        audit: args.audit,
+    };
+    // After Ctrl-C the remaining rows are not matched
+    let mut results: Vec<MatchedRow> = data_rows
         .into_par_iter()
-        .map(|row| match_product_row(row, &migel_items, &keyword_index))
-        .collect();
+        .map(|row| (!interrupt::interrupted()).then(|| matcher.match_row(row)))
+        .collect::<Option<_>>()
+        .ok_or(Fb2SqliteError::Interrupted)?;
+    if let (true, Some(path), Some(overrides)) = (args.review, &args.overrides, &mut overrides) {
+        review_matches(&mut results, overrides, path, args.review_below)?;
+    }
+
+    let match_count = results.iter().filter(|r| r.migel.is_some()).count();
+    let used_items = results
+        .iter()
+        .filter_map(|r| r.migel.map(|(item, _)| item.position_nr.as_str()))
+        .collect::<HashSet<_>>()
+        .len();
+    let skip_count = |reason| results.iter().filter(|r| r.skip_reason == Some(reason)).count();
+    let no_description_count = skip_count(SkipReason::NoDescription);
+    let too_few_tokens_count = skip_count(SkipReason::TooFewTokens);
+    let blocked_count = skip_count(SkipReason::Blocked);
+
+    let sample = args
+        .sample
+        .map(|n| sample_matches(&results, n, args.sample_seed));
+    let histogram = args.score_histogram.then(|| score_histogram(&results));
+    let distribution = args
+        .match_distribution
+        .then(|| match_distribution(&results));
+    let audit = args.audit.then(|| audit_entries(&results));
+    if let Some(path) = &args.list_matches_csv {
+        write_matches_csv(path, &results)?;
+    }
 
-    let match_count = results.iter().filter(|(_, matched)| *matched).count();
+    // Only the CSV columns of the written (matched) rows are candidates
+    let pruner = args.db.prune_empty_columns.then(|| {
+        let mut pruner = ColumnPruner::new(input_columns);
+        for result in results.iter().filter(|r| r.migel.is_some()) {
+            pruner.observe(result.row.iter().map(String::as_str));
+        }
+        pruner.report(&headers);
+        pruner
+    });
 
     // 6. Write matched results to SQLite (sequential — SQLite is single-writer)
     println!("Writing {} matched rows to database...", match_count);
     let (tx, rx) = mpsc::channel::<Vec<String>>();
 
-    let db_fn = db_filename.clone();
-    let db_handle = thread::spawn(move || -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut conn = Connection::open(&db_fn)?;
-        let tx_db = conn.transaction()?;
+    let build_path = build_path(&db_filename, &args.db)?;
+    let db_handle = spawn_output_writer(build_path.clone(), rx, &args.db, &renames, fts_names);
 
-        if let Ok(headers) = rx.recv() {
-            let create_cols = headers
-                .iter()
-                .map(|h| {
-                    format!(
-                        "\"{}\" TEXT",
-                        h.replace(|c: char| !c.is_alphanumeric(), "_")
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join(", ");
-
-            tx_db.execute("DROP TABLE IF EXISTS data", [])?;
-            tx_db.execute(&format!("CREATE TABLE data ({})", create_cols), [])?;
-
-            let placeholders = vec!["?"; headers.len()].join(", ");
-            let query = format!("INSERT INTO data VALUES ({})", placeholders);
-
-            {
-                let mut stmt = tx_db.prepare(&query)?;
-                while let Ok(row) = rx.recv() {
-                    stmt.execute(rusqlite::params_from_iter(row))?;
+    let archive = start_archive(&args.archive, &renames);
+    if let Some((_, archive_tx, _)) = &archive {
+        archive_tx.send(headers.clone())?;
+    }
+    if let Some(pruner) = &pruner {
+        pruner.apply(&mut headers);
+    }
+    tx.send(headers)?;
+    let mut coverage: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    if args.coverage_report {
+        for item in &migel_items {
+            coverage.entry(migel_category(&item.position_nr)).or_default().0 += 1;
+        }
+    }
+    let mut links: Vec<(String, String, f64, usize)> = Vec::new();
+    for mut result in results {
+        if interrupt::interrupted() {
+            break;
+        }
+        if let Some((item, _)) = result.migel {
+            let code = &item.position_nr;
+            append_computed(&mut result.row, &computed);
+            let gtin = result.row.first().cloned().unwrap_or_default();
+            for (rank, (alt_code, score)) in result.alternatives.iter().enumerate() {
+                links.push((gtin.clone(), alt_code.clone(), *score, rank + 1));
+            }
+            if args.coverage_report {
+                if let Some(entry) = coverage.get_mut(migel_category(code)) {
+                    entry.1 += 1;
                 }
             }
+            if let Some((_, archive_tx, _)) = &archive {
+                archive_tx.send(result.row.clone())?;
+            }
+            if let Some(pruner) = &pruner {
+                pruner.apply(&mut result.row);
+            }
+            tx.send(result.row)?;
         }
-        tx_db.commit()?;
-        Ok(())
-    });
+    }
+    drop(tx);
 
-    tx.send(headers)?;
-    for (row, matched) in results {
-        if matched {
-            tx.send(row)?;
+    let inserted = join_db_writer(db_handle, &db_filename);
+    finish_archive(archive)?;
+    let inserted = inserted?;
+    if args.multi_match {
+        write_product_migel(&build_path, &links)?;
+    }
+    if let Some(entries) = &audit {
+        write_match_audit(&build_path, entries, options.min_score)?;
+    }
+    finish_db(&build_path, &args.db)?;
+    publish_db(&build_path, &db_filename)?;
+
+    let unmatched =
+        total_rows - match_count - no_description_count - too_few_tokens_count - blocked_count;
+    // An empty feed counts as rate 0, so --min-match-rate stops it too
+    let match_rate = if total_rows > 0 {
+        match_count as f64 / total_rows as f64
+    } else {
+        0.0
+    };
+    report.line(format!(
+        "Database {} created successfully with {} rows.",
+        db_filename, inserted
+    ));
+    report.line(format!(
+        "Total data rows: {}, MiGeL matches: {} ({:.1}%)",
+        total_rows,
+        match_count,
+        match_rate * 100.0
+    ));
+    report.line(format!("Used {} of {} MiGeL items", used_items, migel_items.len()));
+    report.line(format!(
+        "Unmatched: {} with a description, {} without any description",
+        unmatched, no_description_count
+    ));
+    report.set("database", db_filename.as_str());
+    report.set("rows", inserted);
+    report.set("data_rows", total_rows);
+    report.set("matches", match_count);
+    report.set("match_rate", match_rate);
+    report.set("used_items", used_items);
+    report.set("migel_items", migel_items.len());
+    report.set("unmatched", unmatched);
+    report.set("without_description", no_description_count);
+    if options.min_product_tokens > 1 {
+        report.line(format!(
+            "Skipped for insufficient description (fewer than {} tokens): {}",
+            options.min_product_tokens, too_few_tokens_count
+        ));
+        report.set("too_few_tokens", too_few_tokens_count);
+    }
+    if args.blocklist.is_some() {
+        report.line(format!("Blocked by --blocklist: {}", blocked_count));
+        report.set("blocked", blocked_count);
+    }
+    if args.input.skip_bad_rows {
+        report.line(format!("Skipped malformed CSV records: {}", skipped));
+        report.set("skipped_records", skipped);
+    }
+
+    if let Some(sample) = sample {
+        report.line(format!(
+            "Sample of {} matched rows (seed {}):",
+            sample.len(),
+            args.sample_seed
+        ));
+        for line in &sample {
+            report.line(line);
         }
+        report.set("sample", sample.iter().map(|l| l.trim()).collect::<Vec<_>>());
+    }
+
+    if let Some(histogram) = histogram {
+        report.line("Match score histogram:");
+        let largest = histogram.iter().copied().max().unwrap_or(0).max(1);
+        // Buckets below the lowest score are left out (the thresholds keep them empty)
+        let first = histogram.iter().position(|&n| n > 0).unwrap_or(0);
+        let mut buckets = Vec::new();
+        for (bucket, &count) in histogram.iter().enumerate().skip(first) {
+            let from = bucket as f64 / HISTOGRAM_BUCKETS as f64;
+            let to = (bucket + 1) as f64 / HISTOGRAM_BUCKETS as f64;
+            let bar = "#".repeat((count * HISTOGRAM_BAR).div_ceil(largest));
+            let line = format!("  {:.1}-{:.1} {:>8}  {}", from, to, count, bar);
+            report.line(line.trim_end());
+            buckets.push(serde_json::json!({ "from": from, "to": to, "matches": count }));
+        }
+        report.set("score_histogram", buckets);
+    }
+
+    if let Some(distribution) = distribution {
+        report.line("Matches per MiGeL item:");
+        report.line(format!(
+            "  {:<14} {:>8} {:>9}  bezeichnung",
+            "position_nr", "products", "avg score"
+        ));
+        let mut items = Vec::new();
+        for (item, products, average) in &distribution {
+            report.line(format!(
+                "  {:<14} {:>8} {:>9.2}  {}",
+                item.position_nr, products, average, item.bezeichnung
+            ));
+            items.push(serde_json::json!({
+                "position_nr": item.position_nr,
+                "bezeichnung": item.bezeichnung,
+                "products": products,
+                "average_score": average,
+            }));
+        }
+        report.set("match_distribution", items);
     }
-    drop(tx);
 
-    db_handle
-        .join()
-        .map_err(|_| "Database thread panicked")?
-        .map_err(|e| e.to_string())?;
+    if args.coverage_report {
+        report.line("MiGeL coverage by category:");
+        report.line(format!("  {:<10} {:>8} {:>10}", "category", "items", "products"));
+        let mut categories = serde_json::Map::new();
+        for (category, (items, products)) in &coverage {
+            report.line(format!("  {:<10} {:>8} {:>10}", category, items, products));
+            categories.insert(
+                category.to_string(),
+                serde_json::json!({ "items": items, "products": products }),
+            );
+        }
+        let uncovered = coverage.values().filter(|(_, products)| *products == 0).count();
+        report.line(format!(
+            "  {} of {} categories have no matched products",
+            uncovered,
+            coverage.len()
+        ));
+        report.set("coverage", categories);
+    }
+
+    let stdout = report.finish(stdout)?;
+    if let Some(floor) = args.min_match_rate.filter(|&floor| match_rate < floor) {
+        // The local file stays for inspection, it is just not published
+        return Err(Fb2SqliteError::Validation(format!(
+            "MiGeL match rate {:.1}% ({} of {} rows) is below --min-match-rate {:.1}%; \
+             check the MiGeL sheet layout and the description columns",
+            match_rate * 100.0,
+            match_count,
+            total_rows,
+            floor * 100.0
+        ))
+        .into());
+    }
+    if let Some(stdout) = stdout {
+        let bytes = stdout.stream_file(&db_filename)?;
+        println!("Wrote {} bytes to stdout", bytes);
+        return Ok(());
+    }
+
+    // 7. SCP Transfer (only when deploying)
+    if args.deploy {
+        interrupt::check()?;
+        let upload_options = args.upload.deploy_options(&args.db);
+        upload_db(&[&db_filename], &args.upload.destination(), &upload_options)?;
+    }
+
+    Ok(())
+}
+
+/// Decode the raw CSV bytes into a UTF-8 string.
+/// A UTF-8 BOM means UTF-8 regardless of `--encoding` and is stripped, so the
+/// first header name does not get a `\u{feff}` prefix. Invalid sequences are
+/// replaced with U+FFFD and reported as a warning.
+fn decode_csv(bytes: &[u8], encoding: CsvEncoding) -> String {
+    let (encoding, body) = if let Some(body) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        println!("Stripped UTF-8 BOM from CSV");
+        (encoding_rs::UTF_8, body)
+    } else {
+        let encoding = match encoding {
+            CsvEncoding::Utf8 => encoding_rs::UTF_8,
+            // encoding_rs treats ISO-8859-1 as its superset windows-1252 (WHATWG)
+            CsvEncoding::Latin1 => encoding_rs::WINDOWS_1252,
+        };
+        (encoding, bytes)
+    };
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+    if had_errors {
+        eprintln!(
+            "Warning: CSV contains invalid {} sequences (replaced with U+FFFD); try --encoding latin1",
+            encoding.name()
+        );
+    }
+    text.into_owned()
+}
+
+/// Print the sheets of the MiGeL workbook with their size and header row.
+fn print_sheets(path: &str) -> Result<(), Box<dyn Error>> {
+    for sheet in list_sheets(path)? {
+        println!("{} ({} rows x {} columns)", sheet.name, sheet.rows, sheet.columns);
+        for (idx, name) in sheet.header.iter().enumerate() {
+            if !name.is_empty() {
+                println!("  {:>2}: {}", idx, name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `migel --parse-only`: summarize the keywords per sheet language of the
+/// items `load_migel` parsed. A language sheet that contributed to no item
+/// is reported (likely a changed sheet order); no items at all is an error.
+fn check_parsed_items(items: &[MigelItem], matching: &MatchArgs) -> Result<(), Box<dyn Error>> {
+    if items.is_empty() {
+        return Err(
+            Fb2SqliteError::Parse("No MiGeL items with position numbers found".into()).into(),
+        );
+    }
+    let with_keywords = |keywords: fn(&MigelItem) -> &[String]| {
+        items.iter().filter(|i| !keywords(i).is_empty()).count()
+    };
+    let mut languages = vec![("DE", with_keywords(|i| &i.keywords_de))];
+    // --migel-csv has German texts only
+    if matching.migel_csv.is_none() {
+        if matching.sheet_languages.contains(&Language::Fr) {
+            languages.push(("FR", with_keywords(|i| &i.keywords_fr)));
+        }
+        if matching.sheet_languages.contains(&Language::It) {
+            languages.push(("IT", with_keywords(|i| &i.keywords_it)));
+        }
+    }
+    let counts: Vec<String> = languages
+        .iter()
+        .map(|(name, count)| format!("{} {}", name, count))
+        .collect();
+    println!("MiGeL items with keywords: {}", counts.join(", "));
+    for (name, _) in languages.iter().filter(|(_, count)| *count == 0) {
+        eprintln!(
+            "Warning: no MiGeL item got {} keywords; check the sheets with --list-sheets",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Match a single description given on the command line and print the top candidates.
+fn run_match(
+    args: &MatchCommandArgs,
+    work_dir: &Path,
+    client: &Client,
+) -> Result<(), Box<dyn Error>> {
+    let options = args.matching.options()?;
+    let (migel_items, keyword_index) = load_migel(&args.matching, &options, work_dir, client)?;
 
-    println!("Database {} created successfully.", db_filename);
+    // The language of ad-hoc text is unknown, so score it against all three
+    let text = &args.description;
+    let tokens = product_token_count(text, text, text, "");
+    if tokens < options.min_product_tokens {
+        println!(
+            "Too little text to match \"{}\": {} keyword tokens, --min-product-tokens is {}",
+            text, tokens, options.min_product_tokens
+        );
+        return Ok(());
+    }
+    let matches = rank_migel_matches(
+        text,
+        text,
+        text,
+        "",
+        &migel_items,
+        &keyword_index,
+        &options,
+        args.top,
+    );
+    if matches.is_empty() {
+        println!("No MiGeL match for \"{}\"", text);
+        return Ok(());
+    }
+    for (rank, m) in matches.iter().enumerate() {
+        let edition = m.item.edition.as_ref().map(|e| format!(" ({})", e));
+        println!(
+            "{:>2}. {}{}  score {:.2}  {}  [{}]",
+            rank + 1,
+            m.item.position_nr,
+            edition.unwrap_or_default(),
+            m.score,
+            m.item.bezeichnung,
+            m.matched_keywords.join(" ")
+        );
+    }
+    Ok(())
+}
+
+/// Decode a feed file; JSON feeds are converted to CSV text so both formats
+/// share the record handling of the runners.
+fn decode_feed(bytes: &[u8], input: &InputArgs) -> Result<String, Box<dyn Error>> {
+    let text = decode_csv(bytes, input.encoding);
+    match input.input_format {
+        InputFormat::Csv => Ok(text),
+        InputFormat::Json => json_feed::json_to_csv(&text),
+    }
+}
+
+/// `explain <gtin>`: find the product in the feed, match it and print its
+/// texts and the chosen MiGeL item with the matched keywords in brackets.
+fn run_explain(
+    args: &ExplainArgs,
+    work_dir: &Path,
+    client: &Client,
+) -> Result<(), Box<dyn Error>> {
+    let content = load_csv(&args.input, work_dir, client)?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(Cursor::new(content));
+    let mut records = reader.records();
+    let header: Vec<String> = records
+        .next()
+        .ok_or_else(no_rows)??
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let columns = resolve_match_columns(&header, &args.columns)?;
+
+    let wanted = gtin_key(&args.gtin);
+    let mut skipped = 0;
+    let mut row = None;
+    for result in records {
+        let Some(record) = check_record(result, args.input.skip_bad_rows, &mut skipped)? else {
+            continue;
+        };
+        if record.get(0).is_some_and(|code| gtin_key(code) == wanted) {
+            row = Some(record.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+            break;
+        }
+    }
+    let row = row.ok_or_else(|| {
+        Fb2SqliteError::Validation(format!("GTIN {} not found in the feed", args.gtin))
+    })?;
+    let (desc_de, desc_fr, desc_it, brand) = columns.texts(&row);
+
+    let options = args.matching.options()?;
+    let (migel_items, keyword_index) = load_migel(&args.matching, &options, work_dir, client)?;
+    let best = find_best_migel_match(
+        &desc_de,
+        &desc_fr,
+        &desc_it,
+        &brand,
+        &migel_items,
+        &keyword_index,
+        &options,
+    );
+    let keywords = best.as_ref().map(|m| m.matched_keywords.clone()).unwrap_or_default();
+
+    let boundary = options.word_boundary;
+    println!("Product {}", args.gtin);
+    println!("  DE:    {}", highlight_keywords(&desc_de, &keywords, true, boundary));
+    println!("  FR:    {}", highlight_keywords(&desc_fr, &keywords, false, boundary));
+    println!("  IT:    {}", highlight_keywords(&desc_it, &keywords, false, boundary));
+    println!("  Brand: {}", highlight_keywords(&brand, &keywords, true, boundary));
+    let Some(best) = best else {
+        println!("No MiGeL match");
+        return Ok(());
+    };
+    let language = match best.language {
+        Language::De => "DE",
+        Language::Fr => "FR",
+        Language::It => "IT",
+    };
+    println!(
+        "MiGeL {} (score {:.2}, matched on the {} text)",
+        best.item.position_nr, best.score, language
+    );
+    // Only the German Bezeichnung is kept, so it shows the DE keywords
     println!(
-        "Total data rows: {}, MiGeL matches: {}",
-        total_rows, match_count
+        "  Bezeichnung: {}",
+        highlight_keywords(&best.item.bezeichnung, &keywords, true, boundary)
     );
+    if !best.item.limitation.is_empty() {
+        println!("  Limitation:  {}", best.item.limitation);
+    }
+    println!("  Matched keywords: {}", keywords.join(", "));
+    Ok(())
+}
 
-    // 7. SCP Transfer (only when deploying)
-    if deploy {
-        let remote_dest = "zdavatz@65.109.137.20:/var/www/pillbox.oddb.org/";
-        println!("Transferring {} to {}...", db_filename, remote_dest);
+/// Score thresholds swept by `tune`.
+const TUNE_THRESHOLDS: [f64; 7] = [0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8];
 
-        let status = Command::new("scp")
-            .arg(&db_filename)
-            .arg(remote_dest)
-            .status()?;
+/// `tune --labels`: match the labeled products of the feed at each of
+/// `TUNE_THRESHOLDS` and print precision, recall and F1 against the labels.
+fn run_tune(args: &TuneArgs, work_dir: &Path, client: &Client) -> Result<(), Box<dyn Error>> {
+    // GTIN -> expected position number (empty: the product must stay unmatched)
+    let mut labels = HashMap::new();
+    let mut reader = ReaderBuilder::new().flexible(true).from_path(&args.labels)?;
+    for result in reader.records() {
+        let record = result?;
+        let gtin = gtin_key(record.get(0).unwrap_or(""));
+        if !gtin.is_empty() {
+            labels.insert(gtin, record.get(1).unwrap_or("").trim().to_string());
+        }
+    }
+    if labels.is_empty() {
+        let message = format!("{} contains no labels", args.labels);
+        return Err(Fb2SqliteError::Validation(message).into());
+    }
+
+    let content = load_csv(&args.input, work_dir, client)?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(Cursor::new(content));
+    let mut records = reader.records();
+    let header: Vec<String> = records
+        .next()
+        .ok_or_else(no_rows)??
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let columns = resolve_match_columns(&header, &args.columns)?;
+    let mut skipped = 0;
+    let mut products = Vec::new();
+    for result in records {
+        let Some(record) = check_record(result, args.input.skip_bad_rows, &mut skipped)? else {
+            continue;
+        };
+        let row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        if let Some(expected) = row.first().and_then(|code| labels.remove(&gtin_key(code))) {
+            products.push((columns.texts(&row), expected));
+        }
+    }
+    if !labels.is_empty() {
+        println!("Warning: {} labeled GTINs are not in the feed", labels.len());
+    }
+    let positives = products.iter().filter(|(_, expected)| !expected.is_empty()).count();
+    println!(
+        "Labeled products: {} ({} with a MiGeL code, {} expected unmatched)",
+        products.len(),
+        positives,
+        products.len() - positives
+    );
 
-        if status.success() {
-            println!("SCP transfer complete.");
+    let mut options = args.matching.options()?;
+    let (migel_items, keyword_index) = load_migel(&args.matching, &options, work_dir, client)?;
+    println!("threshold  matched  correct  precision  recall     F1");
+    let mut best: Option<(f64, f64)> = None;
+    for threshold in TUNE_THRESHOLDS {
+        options.min_score = threshold;
+        let (mut matched, mut correct) = (0, 0);
+        for ((desc_de, desc_fr, desc_it, brand), expected) in &products {
+            if product_token_count(desc_de, desc_fr, desc_it, brand) < options.min_product_tokens {
+                continue;
+            }
+            let Some(m) = find_best_migel_match(
+                desc_de,
+                desc_fr,
+                desc_it,
+                brand,
+                &migel_items,
+                &keyword_index,
+                &options,
+            ) else {
+                continue;
+            };
+            matched += 1;
+            if m.item.position_nr == *expected {
+                correct += 1;
+            }
+        }
+        let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let precision = ratio(correct, matched);
+        let recall = ratio(correct, positives);
+        let f1 = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
         } else {
-            return Err(format!("SCP failed with exit code: {:?}", status.code()).into());
+            0.0
+        };
+        println!(
+            "{:>9.2}  {:>7}  {:>7}  {:>9.3}  {:>6.3}  {:>5.3}",
+            threshold, matched, correct, precision, recall, f1
+        );
+        if best.is_none_or(|(_, best_f1)| f1 > best_f1) {
+            best = Some((threshold, f1));
         }
     }
+    if let Some((threshold, f1)) = best {
+        println!("Best F1 {:.3} at --min-score {:.1}", f1, threshold);
+    }
+    Ok(())
+}
 
+/// `migel --compare-matchers`: read the products like `tune` and let
+/// `compare::compare_matchers` run the variants over all of them.
+fn run_compare_matchers(
+    args: &MigelArgs,
+    work_dir: &Path,
+    client: &Client,
+) -> Result<(), Box<dyn Error>> {
+    if args.compare_matchers.len() < 2 {
+        return Err(Fb2SqliteError::Validation(
+            "--compare-matchers needs at least two matchers, e.g. default,phonetic".into(),
+        )
+        .into());
+    }
+    let content = load_csv(&args.input, work_dir, client)?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(Cursor::new(content));
+    let mut records = reader.records();
+    let header: Vec<String> = records
+        .next()
+        .ok_or_else(no_rows)??
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let columns = resolve_match_columns(&header, &args.columns)?;
+    let date_filter = resolve_date_filter(&args.since, &header)?;
+    let mut skipped = 0;
+    let mut products = Vec::new();
+    for result in records {
+        let Some(record) = check_record(result, args.input.skip_bad_rows, &mut skipped)? else {
+            continue;
+        };
+        if date_filter
+            .as_ref()
+            .is_some_and(|filter| !filter.keeps(record.iter()))
+        {
+            continue;
+        }
+        let row: Vec<String> = record.iter().map(|s| s.to_string()).collect();
+        let (desc_de, desc_fr, desc_it, brand) = columns.texts(&row);
+        let description = [&desc_de, &desc_fr, &desc_it, &brand]
+            .into_iter()
+            .find(|text| !text.trim().is_empty())
+            .map_or(String::new(), |text| single_line(text));
+        products.push(Product {
+            gtin: row.first().cloned().unwrap_or_default(),
+            description,
+            row,
+        });
+    }
+
+    let base = args.matching.options()?;
+    let blocklist = args.blocklist.as_deref().map(Blocklist::load).transpose()?;
+    let overrides = args.overrides.as_deref().map(Overrides::load).transpose()?;
+    let (migel_items, keyword_index) = load_migel(&args.matching, &base, work_dir, client)?;
+    let options: Vec<MatchOptions> = args
+        .compare_matchers
+        .iter()
+        .map(|variant| variant.options(&base))
+        .collect();
+    // Every variant matches like a `migel` run: same skips, blocklist,
+    // overrides and output columns (the GTIN the overrides are keyed by)
+    let matchers = options
+        .iter()
+        .map(|options| {
+            Ok(RowMatcher {
+                migel_items: &migel_items,
+                keyword_index: &keyword_index,
+                options,
+                columns,
+                explain: false,
+                output_columns: OutputColumns::new(
+                    &header,
+                    &args.db.columns,
+                    args.input.merged(),
+                )?,
+                multi_match: None,
+                max_limitation_length: None,
+                overrides: overrides.as_ref(),
+                blocklist: blocklist.as_ref(),
+                edition: false,
+                audit: false,
+            })
+        })
+        .collect::<Result<Vec<RowMatcher>, Box<dyn Error>>>()?;
+    compare_matchers(&products, &args.compare_matchers, |idx, product| {
+        matchers[idx].match_row(product.row.clone()).migel
+    });
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+/// Default product feed, used when no `--source-url` is given.
+const GS1_FEED_URL: &str = "https://id.gs1.ch/01/07612345000961";
 
-    let url = "https://id.gs1.ch/01/07612345000961";
-    let csv_filename = "firstbase.csv";
+/// `--min-csv-bytes`: refuse a feed that is too small to be real (an empty
+/// body or an error stub would otherwise replace a good database).
+fn check_feed_size(size: u64, min_bytes: u64, what: &str) -> Result<(), Box<dyn Error>> {
+    if size < min_bytes {
+        return Err(Fb2SqliteError::Validation(format!(
+            "{} has only {} bytes (minimum {}); refusing a truncated feed \
+             (lower --min-csv-bytes if this is expected)",
+            what, size, min_bytes
+        ))
+        .into());
+    }
+    Ok(())
+}
 
-    let content = if args.local_csv {
+/// Download one feed into `csv_filename` (or read the cached file with
+/// `--local-csv`) and decode it.
+fn fetch_feed(
+    url: &str,
+    csv_filename: &str,
+    input: &InputArgs,
+    client: &Client,
+) -> Result<String, Box<dyn Error>> {
+    if input.local_csv {
         // Use locally cached CSV file
         println!("Reading local CSV from {}...", csv_filename);
-        fs::read_to_string(csv_filename)?
+        check_feed_size(fs::metadata(csv_filename)?.len(), input.min_csv_bytes, csv_filename)?;
     } else {
-        // Download and save CSV
+        // Stream the body straight into the file instead of buffering the
+        // whole response; both modes then read the file like --local-csv.
+        // The download lands in a .part file first, so a truncated feed
+        // does not overwrite the cached copy.
         println!("Downloading CSV to {}...", csv_filename);
-        let client = reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(300))
-            .build()?;
-        let response = client.get(url).send()?;
-        let content = response.text()?;
-        {
-            let mut file = std::fs::File::create(csv_filename)?;
-            file.write_all(content.as_bytes())?;
+        let mut request = client.get(url);
+        if input.input_format == InputFormat::Json {
+            request = request.header(reqwest::header::ACCEPT, "application/json");
+        }
+        let mut response = send_checked(request, "GS1 feed")?;
+        let part_filename = format!("{}.part", csv_filename);
+        let mut file = BufWriter::new(fs::File::create(&part_filename)?);
+        // A connection dropped mid-body surfaces as an IO error of the copy
+        let size = io::copy(&mut response, &mut file).map_err(|e| {
+            Fb2SqliteError::Download(format!("Download of {} interrupted: {}", url, e))
+        })?;
+        file.flush()?;
+        drop(file);
+        println!("CSV downloaded ({} bytes)", size);
+        if let Err(e) = check_feed_size(size, input.min_csv_bytes, url) {
+            fs::remove_file(&part_filename)?;
+            return Err(e);
         }
-        content
+        fs::rename(&part_filename, csv_filename)?;
+        println!("CSV saved to {}", csv_filename);
+    }
+    let bytes = fs::read(csv_filename)?;
+    decode_feed(&bytes, input)
+}
+
+/// Download (or read the cached) GS1 feed, or read `--csv-file`, and decode
+/// it. Several `--source-url` feeds are cached as `firstbase_<n>.csv` and
+/// merged into one CSV with a `source` column.
+fn load_csv(
+    input: &InputArgs,
+    work_dir: &Path,
+    client: &Client,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(path) = &input.csv_file {
+        println!("Reading CSV from {}...", path);
+        let bytes = fs::read(path)?;
+        return decode_feed(&bytes, input);
+    }
+    let extension = match input.input_format {
+        InputFormat::Csv => "csv",
+        InputFormat::Json => "json",
     };
+    if !input.merged() {
+        let url = input.source_url.first().map_or(GS1_FEED_URL, String::as_str);
+        let csv_path = work_path(work_dir, &format!("firstbase.{}", extension));
+        return fetch_feed(url, &csv_path, input, client);
+    }
+    let mut feeds = Vec::new();
+    for (n, url) in input.source_url.iter().enumerate() {
+        let csv_path = work_path(work_dir, &format!("firstbase_{}.{}", n + 1, extension));
+        feeds.push((url.clone(), fetch_feed(url, &csv_path, input, client)?));
+    }
+    sources::merge_feeds(&feeds, input.skip_bad_rows)
+}
 
-    if args.migel {
-        run_migel(&content, args.deploy)?;
-    } else {
-        run_normal(&content)?;
+/// Reject options that need a SQLite output with `--output-format parquet`:
+/// the DbArgs post-processing and the subcommand's `options` (name, set).
+fn check_output_format(db: &DbArgs, options: &[(&str, bool)]) -> Result<(), Box<dyn Error>> {
+    if db.output_format == OutputFormat::Sqlite {
+        return Ok(());
+    }
+    let sqlite_only = [
+        ("--vacuum", db.vacuum),
+        ("--analyze", db.analyze),
+        ("--fts", db.fts),
+        ("--sqlite-cache-mb", db.sqlite_cache_mb.is_some()),
+        ("--sqlite-mmap-mb", db.sqlite_mmap_mb.is_some()),
+        ("--table-name", db.table_name != DEFAULT_TABLE),
+    ];
+    if let Some((name, _)) = sqlite_only.iter().chain(options).find(|(_, set)| *set) {
+        return Err(Fb2SqliteError::Validation(format!(
+            "{} needs --output-format sqlite",
+            name
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Take over stdout for `--output -` (the database) or `--report-format json`
+/// (the summary); both at once would mix their bytes.
+fn capture_stdout(
+    db: &DbArgs,
+    report_format: ReportFormat,
+) -> Result<Option<StdoutSink>, Box<dyn Error>> {
+    let json = report_format == ReportFormat::Json;
+    if json && db.to_stdout() {
+        return Err(Fb2SqliteError::Validation(
+            "--report-format json cannot be combined with --output -".into(),
+        )
+        .into());
+    }
+    Ok((json || db.to_stdout()).then(StdoutSink::capture).transpose()?)
+}
+
+/// Exit with the code of the error category (see `Fb2SqliteError::exit_code`).
+fn main() -> ExitCode {
+    if let Err(e) = run() {
+        // After the watchdog stopped the run, the error is just that stop
+        let e: Box<dyn Error> = match watchdog::timed_out() {
+            Some(limit) => Fb2SqliteError::Timeout(limit).into(),
+            None => e,
+        };
+        eprintln!("Error: {}", e);
+        return ExitCode::from(error::exit_code(e.as_ref()));
+    }
+    ExitCode::SUCCESS
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse_from(cli::rewrite_legacy_args(std::env::args().collect()));
+    if let Some(limit) = cli.max_runtime {
+        watchdog::start(limit);
+    }
+
+    let work_dir = cli.work_dir.as_deref().unwrap_or(Path::new(""));
+    if !work_dir.as_os_str().is_empty() {
+        fs::create_dir_all(work_dir)?;
+    }
+    let client = http_client(&cli.http.user_agent, &cli.http.headers)?;
+
+    match &cli.command {
+        Command::Import(args) => {
+            check_output_format(
+                &args.db,
+                &[("--check-remote-schema", args.upload.check_remote_schema)],
+            )?;
+            if args.db.fts {
+                check_fts5()?;
+            }
+            interrupt::install()?;
+            let stdout = capture_stdout(&args.db, args.report_format)?;
+            let content = load_csv(&args.input, work_dir, &client)?;
+            if args.skip_unchanged {
+                let hash_file = work_path(work_dir, FEED_HASH_FILE);
+                let Some(hash) = changed_feed_hash(&content, &hash_file) else {
+                    println!("Feed unchanged, skipping build/upload");
+                    let mut report = Report::new(args.report_format);
+                    report.set("unchanged", true);
+                    report.finish(stdout)?;
+                    return Ok(());
+                };
+                run_normal(&content, args, work_dir, stdout)?;
+                // Only a completed run counts as processed
+                fs::write(&hash_file, hash)?;
+            } else {
+                run_normal(&content, args, work_dir, stdout)?;
+            }
+        }
+        Command::Migel(args) if args.list_sheets => {
+            let local = [
+                ("--migel-csv", args.matching.migel_csv.is_some()),
+                ("--seed-migel-items", args.matching.seed_migel_items.is_some()),
+            ];
+            if let Some((flag, _)) = local.iter().find(|(_, set)| *set) {
+                return Err(Fb2SqliteError::Validation(format!(
+                    "--list-sheets reads the MiGeL XLSX and cannot be combined with {}",
+                    flag
+                ))
+                .into());
+            }
+            for (edition, path) in migel_files(&args.matching, work_dir, &client)? {
+                if let Some(edition) = edition {
+                    println!("{} ({}):", edition, path);
+                }
+                print_sheets(&path)?;
+            }
+        }
+        Command::Migel(args) if !args.compare_matchers.is_empty() => {
+            run_compare_matchers(args, work_dir, &client)?;
+        }
+        Command::Migel(args) if args.parse_only => {
+            let options = args.matching.options()?;
+            let (migel_items, _) = load_migel(&args.matching, &options, work_dir, &client)?;
+            check_parsed_items(&migel_items, &args.matching)?;
+        }
+        Command::Migel(args) => {
+            if args.deploy && args.db.to_stdout() {
+                return Err(Fb2SqliteError::Validation(
+                    "--output - cannot be combined with --deploy".into(),
+                )
+                .into());
+            }
+            check_output_format(
+                &args.db,
+                &[
+                    ("--multi-match", args.multi_match),
+                    ("--audit", args.audit),
+                    ("--check-remote-schema", args.upload.check_remote_schema),
+                ],
+            )?;
+            if args.db.fts {
+                check_fts5()?;
+            }
+            interrupt::install()?;
+            let stdout = capture_stdout(&args.db, args.report_format)?;
+            let content = load_csv(&args.input, work_dir, &client)?;
+            run_migel(&content, args, work_dir, &client, stdout)?;
+        }
+        Command::Match(args) => {
+            run_match(args, work_dir, &client)?;
+        }
+        Command::Explain(args) => {
+            run_explain(args, work_dir, &client)?;
+        }
+        Command::Tune(args) => {
+            run_tune(args, work_dir, &client)?;
+        }
+        Command::Doctor(args) => {
+            let mut urls: Vec<(&str, &str)> = args
+                .source_url
+                .iter()
+                .map(|url| ("feed", url.as_str()))
+                .collect();
+            if urls.is_empty() {
+                urls.push(("GS1 feed", GS1_FEED_URL));
+            }
+            urls.push(("MiGeL XLSX", MIGEL_URL));
+            doctor::run_doctor(&urls, work_dir, &client)?;
+        }
+        Command::Upload(args) => {
+            let files: Vec<&str> = args.files.iter().map(String::as_str).collect();
+            upload_db(&files, &args.upload.destination(), &args.upload.options())?;
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_csv_strips_the_utf8_bom() {
+        for encoding in [CsvEncoding::Utf8, CsvEncoding::Latin1] {
+            let text = decode_csv(b"\xEF\xBB\xBFgtin,name\n07612345000011,Gaze\n", encoding);
+            let header = csv_header(&text, false).unwrap();
+            assert_eq!(header, ["gtin", "name"]);
+        }
+    }
+}
@@ -1,11 +1,21 @@
+use crate::abbreviations::Abbreviations;
+use crate::error::Fb2SqliteError;
+use crate::number::parse_swiss_number;
+use crate::phonetic::cologne_code;
+use crate::synonyms::Synonyms;
 use calamine::{open_workbook, Reader, Xlsx};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
+use std::ops::Bound;
 
 pub struct MigelItem {
     pub position_nr: String,
+    /// First line of the Bezeichnung as in the source (case and umlauts
+    /// kept); it is only displayed and emitted, matching uses the keywords
     pub bezeichnung: String,
     pub limitation: String,
+    /// Reimbursement amount (CHF) from the amount column, if the sheet has one
+    pub betrag: Option<f64>,
     /// DE first-line keywords (used for primary scoring)
     pub keywords_de: Vec<String>,
     /// FR first-line keywords (used for primary scoring)
@@ -18,8 +28,135 @@ pub struct MigelItem {
     pub secondary_fr: Vec<String>,
     /// IT bonus keywords from additional lines
     pub secondary_it: Vec<String>,
+    /// DE keywords of the enclosing category descriptions that are not among
+    /// the item's own keywords (scored with `MatchOptions.category_weight`)
+    pub category_de: Vec<String>,
     /// Union of all keywords (used for candidate index)
     pub all_keywords: Vec<String>,
+    /// DE keywords were taken from the category context because the
+    /// Bezeichnung reduced to no keywords after stop-word filtering
+    pub category_fallback: bool,
+    /// Primary keywords were cut to `ParseOptions.max_keywords`
+    pub keywords_trimmed: bool,
+    /// Kölner Phonetik codes of the DE keywords of `PHONETIC_MIN_LEN` or more
+    /// chars (candidate lookup for `MatchOptions.phonetic`)
+    pub phonetic_de: Vec<String>,
+    /// Edition label of the `--migel-file` workbook the item comes from
+    /// (`None` for the downloaded workbook)
+    pub edition: Option<String>,
+}
+
+/// Languages of the MiGeL workbook sheets (sheet order: DE, FR, IT).
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Language {
+    De,
+    Fr,
+    It,
+}
+
+/// Options controlling how `parse_migel_items` builds the keyword sets.
+pub struct ParseOptions {
+    /// Languages whose sheets contribute keywords. The German sheet is always
+    /// parsed since it defines the items; FR/IT enrichment is skipped when
+    /// the language is not listed.
+    pub languages: Vec<Language>,
+    /// Manual keywords per position number, merged into the DE primary keywords
+    pub supplements: HashMap<String, Vec<String>>,
+    /// Take the primary keywords from the whole multi-line Bezeichnung cell
+    /// instead of its first line (no separate secondary keywords then)
+    pub full_description: bool,
+    /// Keep at most this many primary keywords per item and language (the
+    /// most distinctive ones), so long keyword lists do not dilute the score
+    pub max_keywords: Option<usize>,
+    /// Use the enclosing category descriptions: as keyword fallback for items
+    /// without own keywords and as `MigelItem.category_de`. Without them an
+    /// item matches on its own Bezeichnung only.
+    pub category_keywords: bool,
+    /// Abbreviations written out in the texts before keywords are extracted
+    pub abbreviations: Abbreviations,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            languages: vec![Language::De, Language::Fr, Language::It],
+            supplements: HashMap::new(),
+            full_description: false,
+            max_keywords: None,
+            category_keywords: true,
+            abbreviations: Abbreviations::default(),
+        }
+    }
+}
+
+/// A matched MiGeL item together with the evidence for the match.
+pub struct MigelMatch<'a> {
+    pub item: &'a MigelItem,
+    /// Score of the winning language (weighted share of matched primary keywords)
+    pub score: f64,
+    /// Keywords that matched in the winning language (primary, then secondary)
+    pub matched_keywords: Vec<String>,
+    /// Language whose keywords gave the score
+    pub language: Language,
+}
+
+/// Order among candidates with the same score. Every variant ends with the
+/// position number, so the winner never depends on hash-map iteration order.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum TieBreak {
+    /// Longest matched keyword first
+    Length,
+    /// Most matched keywords first
+    Count,
+    /// Shortest Bezeichnung first (the most specific item)
+    ShortestDesc,
+    /// Lowest position number first
+    Position,
+}
+
+/// Tunable matching parameters passed to `find_best_migel_match`.
+#[derive(Clone)]
+pub struct MatchOptions {
+    /// Minimum number of distinct matched keywords (primary + secondary) before
+    /// an item is eligible, applied on top of the score thresholds.
+    pub min_matches: usize,
+    /// How equal scores are ordered
+    pub tie_break: TieBreak,
+    /// Products with fewer keyword tokens than this (see `product_token_count`)
+    /// are not matched at all
+    pub min_product_tokens: usize,
+    /// Weight of category context keywords relative to the item's own DE
+    /// keywords (0 = ignore them)
+    pub category_weight: f64,
+    /// Minimum score of a match with two or more keywords; single-keyword
+    /// matches need at least 0.5 as well
+    pub min_score: f64,
+    /// Only keywords of `COMPOUND_MIN_LEN` or more chars may match as the
+    /// end of a German compound; shorter ones must be whole words
+    pub word_boundary: bool,
+    /// DE keywords also match product words with the same Kölner Phonetik
+    /// code, at `PHONETIC_WEIGHT`
+    pub phonetic: bool,
+    /// Synonym groups added to the product text before matching
+    pub synonyms: Synonyms,
+    /// Abbreviations written out in the product text before matching
+    pub abbreviations: Abbreviations,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            min_matches: 1,
+            tie_break: TieBreak::Length,
+            min_product_tokens: 1,
+            category_weight: 0.0,
+            min_score: 0.3,
+            word_boundary: false,
+            phonetic: false,
+            synonyms: Synonyms::default(),
+            abbreviations: Abbreviations::default(),
+        }
+    }
 }
 
 const STOP_WORDS: &[&str] = &[
@@ -63,16 +200,36 @@ pub fn normalize_german(text: &str) -> String {
         .replace('Ä', "Ae")
         .replace('Ö', "Oe")
         .replace('Ü', "Ue")
-        .replace('é', "e")
-        .replace('è', "e")
-        .replace('ê', "e")
-        .replace('à', "a")
-        .replace('â', "a")
-        .replace('ù', "u")
-        .replace('û', "u")
+        .replace(['é', 'è', 'ê'], "e")
+        .replace(['à', 'â'], "a")
+        .replace(['ù', 'û'], "u")
         .replace('ô', "o")
         .replace('î', "i")
         .replace('ç', "c")
+        .replace(['É', 'È', 'Ê'], "E")
+        .replace(['À', 'Â'], "A")
+        .replace(['Ù', 'Û'], "U")
+        .replace('Ô', "O")
+        .replace('Î', "I")
+        .replace('Ç', "C")
+}
+
+/// Lowercase with the Unicode default case mapping (`str::to_lowercase`), not
+/// a locale's: `I` always becomes `i`, also in Turkish text. `İ` maps to `i`
+/// plus a combining dot, which is dropped so it compares like a plain `i`.
+/// Use this instead of calling `to_lowercase` directly.
+pub fn fold_case(text: &str) -> String {
+    text.to_lowercase().replace('\u{307}', "")
+}
+
+/// The one normalization applied to both sides of a match: MiGeL keywords
+/// (`extract_keywords_from`, supplements) and product text
+/// (`rank_migel_matches`, via `Abbreviations::expand`). Never normalize
+/// either side any other way, or keywords and product words stop comparing
+/// equal. Case is folded first, so capitals such as `É` or `Ä` go through the
+/// same accent folding as `é`/`ä`.
+pub fn normalize_for_match(text: &str) -> String {
+    normalize_german(&fold_case(text))
 }
 
 /// Extract search keywords from first line of text (min 3 chars).
@@ -100,7 +257,7 @@ fn extract_secondary_keywords(text: &str) -> Vec<String> {
 
 /// Shared keyword extraction logic.
 fn extract_keywords_from(text: &str, min_len: usize) -> Vec<String> {
-    let normalized = normalize_german(text).to_lowercase();
+    let normalized = normalize_for_match(text);
     let mut keywords: Vec<String> = normalized
         .split(|c: char| !c.is_alphanumeric())
         .filter(|w| w.len() >= min_len)
@@ -112,6 +269,69 @@ fn extract_keywords_from(text: &str, min_len: usize) -> Vec<String> {
     keywords
 }
 
+/// Number of keyword tokens in the richest language of a product (description
+/// plus brand), extracted like MiGeL keywords.
+pub fn product_token_count(desc_de: &str, desc_fr: &str, desc_it: &str, brand: &str) -> usize {
+    [desc_de, desc_fr, desc_it]
+        .iter()
+        .map(|desc| extract_keywords_from(&format!("{} {}", desc, brand), 3).len())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Keywords of the given product texts (all lines), extracted like MiGeL
+/// keywords: normalized, stop words removed, sorted and deduplicated.
+pub fn product_keywords(texts: &[&str]) -> Vec<String> {
+    let mut keywords: Vec<String> = texts.iter().flat_map(|t| extract_keywords_full(t)).collect();
+    keywords.sort();
+    keywords.dedup();
+    keywords
+}
+
+/// Normalize manually supplied keywords like product text (umlauts, case,
+/// word splitting). Stop words are kept since they were chosen on purpose.
+fn normalize_supplied_keywords(words: &[String]) -> Vec<String> {
+    let mut keywords: Vec<String> = words
+        .iter()
+        .flat_map(|w| {
+            normalize_for_match(w)
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    keywords.sort();
+    keywords.dedup();
+    keywords
+}
+
+/// Load a keyword supplement file with one `position_nr: keyword, keyword` entry
+/// per line. Blank lines and lines starting with `#` are ignored; repeated
+/// position numbers accumulate.
+pub fn load_keyword_supplements(path: &str) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut supplements: HashMap<String, Vec<String>> = HashMap::new();
+    for (line_nr, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (pos_nr, words) = line.split_once(':').ok_or_else(|| {
+            Fb2SqliteError::Parse(format!(
+                "{}:{}: expected `position_nr: keywords`",
+                path,
+                line_nr + 1
+            ))
+        })?;
+        supplements
+            .entry(pos_nr.trim().to_string())
+            .or_default()
+            .extend(words.split(',').map(|w| w.trim().to_string()).filter(|w| !w.is_empty()));
+    }
+    Ok(supplements)
+}
+
 /// Read a cell from a calamine row as a trimmed string.
 fn cell_str(row: &[calamine::Data], idx: usize) -> String {
     row.get(idx)
@@ -121,19 +341,118 @@ fn cell_str(row: &[calamine::Data], idx: usize) -> String {
         .to_string()
 }
 
+/// Index of the reimbursement amount column, detected by header name
+/// ("Höchstvergütungsbetrag", "HVB ...") since its position varies between releases.
+fn find_amount_column(header: &[calamine::Data]) -> Option<usize> {
+    header.iter().position(|cell| {
+        let name = fold_case(&cell.to_string());
+        name.contains("betrag") || name.starts_with("hvb")
+    })
+}
+
+/// Name, size and header row of one workbook sheet (`--list-sheets`).
+pub struct SheetInfo {
+    pub name: String,
+    pub rows: usize,
+    pub columns: usize,
+    pub header: Vec<String>,
+}
+
+/// Describe every sheet of the workbook, to check the layout after BAG
+/// publishes a new edition.
+pub fn list_sheets(path: &str) -> Result<Vec<SheetInfo>, Box<dyn Error>> {
+    let mut workbook: Xlsx<_> = open_workbook(path)?;
+    let sheet_names: Vec<String> = workbook.sheet_names().to_vec();
+    let mut sheets = Vec::new();
+    for name in sheet_names {
+        let range = workbook.worksheet_range(&name)?;
+        let (rows, columns) = range.get_size();
+        let header = range
+            .rows()
+            .next()
+            .map(|row| (0..row.len()).map(|i| cell_str(row, i)).collect())
+            .unwrap_or_default();
+        sheets.push(SheetInfo {
+            name,
+            rows,
+            columns,
+            header,
+        });
+    }
+    Ok(sheets)
+}
+
+/// Cut each item's primary keyword lists to the `max` most distinctive
+/// keywords: those used by the fewest items of the same language (IDF), then
+/// the longest. The candidate index (`all_keywords`) keeps all keywords.
+fn trim_keywords(items: &mut [MigelItem], max: usize) {
+    type KeywordList = fn(&mut MigelItem) -> &mut Vec<String>;
+    let languages: [KeywordList; 3] = [
+        |item| &mut item.keywords_de,
+        |item| &mut item.keywords_fr,
+        |item| &mut item.keywords_it,
+    ];
+    for keywords_of in languages {
+        let mut doc_freq: HashMap<String, usize> = HashMap::new();
+        for item in items.iter_mut() {
+            for kw in keywords_of(item).iter() {
+                *doc_freq.entry(kw.clone()).or_default() += 1;
+            }
+        }
+        for item in items.iter_mut() {
+            let keywords = keywords_of(item);
+            if keywords.len() <= max {
+                continue;
+            }
+            keywords.sort_by(|a, b| {
+                doc_freq[a]
+                    .cmp(&doc_freq[b])
+                    .then(b.len().cmp(&a.len()))
+                    .then(a.cmp(b))
+            });
+            keywords.truncate(max);
+            keywords.sort();
+            item.keywords_trimmed = true;
+        }
+    }
+}
+
 /// Parse all MiGeL items (rows with a Positions-Nr.) from the XLSX file.
 /// Keeps per-language keywords separate for scoring, and builds a combined
 /// keyword set for candidate finding.
-pub fn parse_migel_items(path: &str) -> Result<Vec<MigelItem>, Box<dyn Error>> {
+pub fn parse_migel_items(
+    path: &str,
+    options: &ParseOptions,
+) -> Result<Vec<MigelItem>, Box<dyn Error>> {
     let mut workbook: Xlsx<_> = open_workbook(path)?;
     let sheet_names: Vec<String> = workbook.sheet_names().to_vec();
 
-    // --- Pass 1: Parse German sheet (index 0) ---
-    let range_de = workbook.worksheet_range(&sheet_names[0])?;
+    // --- Pass 1: Parse German sheet (index 0), which defines the items ---
+    let Some(sheet_de) = sheet_names.first() else {
+        return Err(Fb2SqliteError::Parse(format!("{}: workbook has no sheets", path)).into());
+    };
+    let range_de = workbook.worksheet_range(sheet_de)?;
 
     // Track category hierarchy descriptions (levels B through G = indices 1..7)
     let mut category_texts: Vec<String> = vec![String::new(); 7];
     let mut items: Vec<MigelItem> = Vec::new();
+    let amount_col = range_de.rows().next().and_then(find_amount_column);
+
+    // With `full_description`, continuation lines count as primary keywords
+    let primary_keywords = |text: &str| {
+        if options.full_description {
+            extract_keywords_full(text)
+        } else {
+            extract_keywords(text)
+        }
+    };
+    let secondary_keywords = |text: &str| {
+        if options.full_description {
+            Vec::new()
+        } else {
+            extract_secondary_keywords(text)
+        }
+    };
 
     for (row_idx, row) in range_de.rows().enumerate() {
         if row_idx == 0 {
@@ -143,16 +462,18 @@ pub fn parse_migel_items(path: &str) -> Result<Vec<MigelItem>, Box<dyn Error>> {
         let pos_nr = cell_str(row, 7); // H = Positions-Nr.
         let bezeichnung = cell_str(row, 9); // J = Bezeichnung
         let limitation = cell_str(row, 10); // K = Limitation
+        // Keywords come from the expanded texts, the item keeps the originals
+        let text = options.abbreviations.expand(&bezeichnung);
+        let limitation_text = options.abbreviations.expand(&limitation);
 
         if pos_nr.is_empty() {
             // Category header row — update hierarchy
             for i in (1..7).rev() {
                 let val = cell_str(row, i);
                 if !val.is_empty() {
-                    category_texts[i] =
-                        bezeichnung.lines().next().unwrap_or("").trim().to_string();
-                    for j in (i + 1)..7 {
-                        category_texts[j] = String::new();
+                    category_texts[i] = text.lines().next().unwrap_or("").trim().to_string();
+                    for text in category_texts.iter_mut().skip(i + 1) {
+                        text.clear();
                     }
                     break;
                 }
@@ -161,16 +482,35 @@ pub fn parse_migel_items(path: &str) -> Result<Vec<MigelItem>, Box<dyn Error>> {
             // Item with position number
             let first_line = bezeichnung.lines().next().unwrap_or("").trim().to_string();
 
-            // DE primary keywords: first line only (used for score ratio)
-            let keywords_de = extract_keywords(&first_line);
+            // DE primary keywords: first line by default (used for score ratio)
+            let mut keywords_de = primary_keywords(&text);
+            // Fallback: an item without own keywords could never match, so use
+            // the most specific category description instead (even a single word)
+            let mut category_fallback = false;
+            if keywords_de.is_empty() && options.category_keywords {
+                if let Some(category) = category_texts.iter().rev().find(|t| !t.is_empty()) {
+                    keywords_de = extract_keywords(category);
+                    category_fallback = !keywords_de.is_empty();
+                }
+            }
             // DE secondary keywords: long keywords from additional lines (bonus matches)
-            let secondary_de = extract_secondary_keywords(&bezeichnung);
+            let secondary_de = secondary_keywords(&text);
+            // Category context: keywords of all enclosing category levels
+            let mut category_de: Vec<String> = category_texts
+                .iter()
+                .filter(|_| options.category_keywords)
+                .flat_map(|text| extract_keywords(text))
+                .filter(|kw| !keywords_de.contains(kw))
+                .collect();
+            category_de.sort();
+            category_de.dedup();
 
             // All keywords: full Bezeichnung text (all lines) + Limitation text
             // for broader candidate finding via the inverted index.
-            let mut all_kw = extract_keywords_full(&bezeichnung);
+            let mut all_kw = extract_keywords_full(&text);
+            all_kw.extend(keywords_de.iter().cloned());
             if !limitation.is_empty() {
-                let lim_kw = extract_keywords_full(&limitation);
+                let lim_kw = extract_keywords_full(&limitation_text);
                 all_kw.extend(lim_kw);
                 all_kw.sort();
                 all_kw.dedup();
@@ -180,13 +520,19 @@ pub fn parse_migel_items(path: &str) -> Result<Vec<MigelItem>, Box<dyn Error>> {
                 position_nr: pos_nr,
                 bezeichnung: first_line,
                 limitation,
+                betrag: amount_col.and_then(|col| parse_swiss_number(&cell_str(row, col))),
                 keywords_de,
                 keywords_fr: Vec::new(),
                 keywords_it: Vec::new(),
                 secondary_de,
                 secondary_fr: Vec::new(),
                 secondary_it: Vec::new(),
+                category_de,
                 all_keywords: all_kw,
+                category_fallback,
+                keywords_trimmed: false,
+                phonetic_de: Vec::new(),
+                edition: None,
             });
         }
     }
@@ -198,20 +544,44 @@ pub fn parse_migel_items(path: &str) -> Result<Vec<MigelItem>, Box<dyn Error>> {
         .map(|(i, item)| (item.position_nr.clone(), i))
         .collect();
 
-    for sheet_idx in 1..sheet_names.len().min(3) {
-        let range = workbook.worksheet_range(&sheet_names[sheet_idx])?;
+    // A missing or unreadable enrichment sheet only costs its language
+    for (sheet_idx, language, name) in [(1, Language::Fr, "French"), (2, Language::It, "Italian")] {
+        if !options.languages.contains(&language) {
+            continue;
+        }
+        let Some(sheet_name) = sheet_names.get(sheet_idx) else {
+            eprintln!(
+                "Warning: {} has no {} sheet (sheet {}), continuing without {} keywords",
+                path,
+                name,
+                sheet_idx + 1,
+                name
+            );
+            continue;
+        };
+        let range = match workbook.worksheet_range(sheet_name) {
+            Ok(range) => range,
+            Err(e) => {
+                eprintln!(
+                    "Warning: could not read the {} sheet \"{}\" of {} ({}), \
+                     continuing without {} keywords",
+                    name, sheet_name, path, e, name
+                );
+                continue;
+            }
+        };
         for (row_idx, row) in range.rows().enumerate() {
             if row_idx == 0 {
                 continue;
             }
             let pos_nr = cell_str(row, 7);
             if let Some(&item_idx) = pos_map.get(&pos_nr) {
-                let bezeichnung = cell_str(row, 9);
-                let limitation = cell_str(row, 10);
-                // Primary scoring keywords: first line only
-                let kw = extract_keywords(&bezeichnung);
+                let bezeichnung = options.abbreviations.expand(&cell_str(row, 9));
+                let limitation = options.abbreviations.expand(&cell_str(row, 10));
+                // Primary scoring keywords: first line by default
+                let kw = primary_keywords(&bezeichnung);
                 // Secondary keywords: long keywords from additional lines
-                let secondary = extract_secondary_keywords(&bezeichnung);
+                let secondary = secondary_keywords(&bezeichnung);
                 match sheet_idx {
                     1 => {
                         items[item_idx].keywords_fr = kw.clone();
@@ -234,19 +604,306 @@ pub fn parse_migel_items(path: &str) -> Result<Vec<MigelItem>, Box<dyn Error>> {
         }
     }
 
+    finish_items(&mut items, options);
+    Ok(items)
+}
+
+/// Steps shared by the XLSX and CSV parsers once the items are read: merge
+/// `ParseOptions.supplements`, trim the keywords to `max_keywords`, dedupe
+/// the candidate keywords and derive the phonetic codes.
+fn finish_items(items: &mut [MigelItem], options: &ParseOptions) {
+    // Merge manual keyword supplements (DE primary + candidate index)
+    for item in items.iter_mut() {
+        if let Some(words) = options.supplements.get(&item.position_nr) {
+            let extra = normalize_supplied_keywords(words);
+            item.keywords_de.extend(extra.iter().cloned());
+            item.keywords_de.sort();
+            item.keywords_de.dedup();
+            item.all_keywords.extend(extra);
+        }
+    }
+
+    if let Some(max) = options.max_keywords {
+        trim_keywords(items, max);
+    }
+
     // Deduplicate all_keywords per item
-    for item in &mut items {
+    for item in items.iter_mut() {
         item.all_keywords.sort();
         item.all_keywords.dedup();
+        item.phonetic_de = phonetic_codes(&item.keywords_de);
+        item.phonetic_de.sort();
+        item.phonetic_de.dedup();
     }
+}
+
+/// Read MiGeL items from a pre-extracted CSV (`--migel-csv`) instead of the
+/// XLSX: a header row naming the columns `position_nr`, `bezeichnung` and
+/// `limitation` (any order and case), optionally `keywords` (comma
+/// separated, merged like `--migel-keywords`) and `betrag`. The texts are
+/// German and there is no category hierarchy, so FR/IT and category
+/// keywords stay empty.
+pub fn parse_migel_csv(
+    path: &str,
+    options: &ParseOptions,
+) -> Result<Vec<MigelItem>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let header: Vec<String> = reader.headers()?.iter().map(|h| fold_case(h.trim())).collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(pos_col), Some(bez_col), Some(lim_col)) = (
+        column("position_nr"),
+        column("bezeichnung"),
+        column("limitation"),
+    ) else {
+        let missing: Vec<&str> = ["position_nr", "bezeichnung", "limitation"]
+            .into_iter()
+            .filter(|name| column(name).is_none())
+            .collect();
+        return Err(Fb2SqliteError::Parse(format!(
+            "{}: missing MiGeL column(s) {}",
+            path,
+            missing.join(", ")
+        ))
+        .into());
+    };
+    let keywords_col = column("keywords");
+    let amount_col = column("betrag");
+
+    let mut items: Vec<MigelItem> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (line_nr, result) in reader.records().enumerate() {
+        let record = result?;
+        let field = |i: usize| record.get(i).unwrap_or("").trim();
+        let pos_nr = field(pos_col);
+        if pos_nr.is_empty() || !seen.insert(pos_nr.to_string()) {
+            return Err(Fb2SqliteError::Parse(format!(
+                "{}:{}: {} position_nr \"{}\"",
+                path,
+                line_nr + 2,
+                if pos_nr.is_empty() { "empty" } else { "duplicate" },
+                pos_nr
+            ))
+            .into());
+        }
+        let bezeichnung = field(bez_col);
+        let limitation = field(lim_col);
+        let text = options.abbreviations.expand(bezeichnung);
+
+        let mut keywords_de = if options.full_description {
+            extract_keywords_full(&text)
+        } else {
+            extract_keywords(&text)
+        };
+        let secondary_de = if options.full_description {
+            Vec::new()
+        } else {
+            extract_secondary_keywords(&text)
+        };
+        let mut all_kw = extract_keywords_full(&text);
+        all_kw.extend(extract_keywords_full(&options.abbreviations.expand(limitation)));
+        if let Some(col) = keywords_col {
+            let words: Vec<String> = field(col)
+                .split(',')
+                .map(|w| w.trim().to_string())
+                .filter(|w| !w.is_empty())
+                .collect();
+            let extra = normalize_supplied_keywords(&words);
+            keywords_de.extend(extra.iter().cloned());
+            keywords_de.sort();
+            keywords_de.dedup();
+            all_kw.extend(extra);
+        }
+        all_kw.extend(keywords_de.iter().cloned());
+
+        items.push(MigelItem {
+            position_nr: pos_nr.to_string(),
+            bezeichnung: bezeichnung.lines().next().unwrap_or("").trim().to_string(),
+            limitation: limitation.to_string(),
+            betrag: amount_col.and_then(|col| parse_swiss_number(field(col))),
+            keywords_de,
+            keywords_fr: Vec::new(),
+            keywords_it: Vec::new(),
+            secondary_de,
+            secondary_fr: Vec::new(),
+            secondary_it: Vec::new(),
+            category_de: Vec::new(),
+            all_keywords: all_kw,
+            category_fallback: false,
+            keywords_trimmed: false,
+            phonetic_de: Vec::new(),
+            edition: None,
+        });
+    }
+    finish_items(&mut items, options);
+    Ok(items)
+}
+
+/// Fields of a `--seed-migel-items` item; anything else is rejected, so a
+/// misspelt keyword list does not silently fall back to extraction.
+const SEED_FIELDS: &[&str] = &[
+    "position_nr",
+    "bezeichnung",
+    "limitation",
+    "betrag",
+    "keywords_de",
+    "keywords_fr",
+    "keywords_it",
+    "secondary_de",
+    "secondary_fr",
+    "secondary_it",
+    "category_de",
+];
 
+/// `--seed-migel-items` (testing aid): read the items from a JSON array of
+/// objects instead of a workbook, so the matcher runs offline on a known
+/// item set. Each object has `position_nr` and `bezeichnung`, optionally
+/// `limitation`, `betrag` and the keyword lists of `MigelItem`
+/// (`SEED_FIELDS`). Given lists are normalized like `--migel-keywords`;
+/// without `keywords_de` the DE keywords come from the Bezeichnung as with
+/// `--migel-csv`. Lists of languages not in `ParseOptions.languages` are
+/// ignored.
+pub fn parse_migel_seed(
+    path: &str,
+    options: &ParseOptions,
+) -> Result<Vec<MigelItem>, Box<dyn Error>> {
+    let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let Some(records) = value.as_array() else {
+        let message = format!("{}: expected a JSON array of MiGeL items", path);
+        return Err(Fb2SqliteError::Parse(message).into());
+    };
+    let mut items: Vec<MigelItem> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (idx, record) in records.iter().enumerate() {
+        let error = |message: String| {
+            Fb2SqliteError::Parse(format!("{}: item {}: {}", path, idx + 1, message))
+        };
+        let Some(fields) = record.as_object() else {
+            return Err(error("not an object".into()).into());
+        };
+        let known = |name: &&String| SEED_FIELDS.contains(&name.as_str());
+        if let Some(name) = fields.keys().find(|name| !known(name)) {
+            return Err(error(format!("unknown field \"{}\"", name)).into());
+        }
+        let text = |name: &str| match fields.get(name) {
+            None | Some(serde_json::Value::Null) => Ok(String::new()),
+            Some(serde_json::Value::String(text)) => Ok(text.trim().to_string()),
+            Some(_) => Err(error(format!("{} is not a string", name))),
+        };
+        let keywords = |name: &str, language: Language| {
+            let words = match fields.get(name) {
+                None | Some(serde_json::Value::Null) => return Ok(None),
+                Some(serde_json::Value::Array(words)) => words,
+                Some(_) => return Err(error(format!("{} is not an array", name))),
+            };
+            let words = words
+                .iter()
+                .map(|w| w.as_str().map(str::to_string))
+                .collect::<Option<Vec<String>>>()
+                .ok_or_else(|| error(format!("{} holds a value that is not a string", name)))?;
+            if !options.languages.contains(&language) {
+                return Ok(Some(Vec::new()));
+            }
+            Ok(Some(normalize_supplied_keywords(&words)))
+        };
+
+        let position_nr = text("position_nr")?;
+        if position_nr.is_empty() {
+            return Err(error("missing position_nr".into()).into());
+        }
+        if !seen.insert(position_nr.clone()) {
+            return Err(error(format!("duplicate position_nr \"{}\"", position_nr)).into());
+        }
+        let bezeichnung = text("bezeichnung")?;
+        let limitation = text("limitation")?;
+        let betrag = match fields.get("betrag") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(amount) => Some(
+                amount
+                    .as_f64()
+                    .ok_or_else(|| error("betrag is not a number".into()))?,
+            ),
+        };
+        let keywords_de = match keywords("keywords_de", Language::De)? {
+            Some(given) => given,
+            None if options.full_description => {
+                extract_keywords_full(&options.abbreviations.expand(&bezeichnung))
+            }
+            None => extract_keywords(&options.abbreviations.expand(&bezeichnung)),
+        };
+        let list = |name: &str, language: Language| {
+            keywords(name, language).map(Option::unwrap_or_default)
+        };
+        let mut item = MigelItem {
+            bezeichnung: bezeichnung.lines().next().unwrap_or("").trim().to_string(),
+            betrag,
+            keywords_de,
+            keywords_fr: list("keywords_fr", Language::Fr)?,
+            keywords_it: list("keywords_it", Language::It)?,
+            secondary_de: list("secondary_de", Language::De)?,
+            secondary_fr: list("secondary_fr", Language::Fr)?,
+            secondary_it: list("secondary_it", Language::It)?,
+            category_de: list("category_de", Language::De)?,
+            all_keywords: extract_keywords_full(&options.abbreviations.expand(&limitation)),
+            position_nr,
+            limitation,
+            category_fallback: false,
+            keywords_trimmed: false,
+            phonetic_de: Vec::new(),
+            edition: None,
+        };
+        // The candidate index holds every keyword the item can score with
+        let own = [
+            &item.keywords_de,
+            &item.keywords_fr,
+            &item.keywords_it,
+            &item.secondary_de,
+            &item.secondary_fr,
+            &item.secondary_it,
+        ];
+        let own: Vec<String> = own.into_iter().flatten().cloned().collect();
+        item.all_keywords.extend(own);
+        items.push(item);
+    }
+    finish_items(&mut items, options);
     Ok(items)
 }
 
+/// Items without any primary keyword in DE/FR/IT. These can never be matched
+/// and need manual keywords.
+pub fn items_without_keywords(items: &[MigelItem]) -> Vec<&MigelItem> {
+    items
+        .iter()
+        .filter(|item| {
+            item.keywords_de.is_empty() && item.keywords_fr.is_empty() && item.keywords_it.is_empty()
+        })
+        .collect()
+}
+
+/// Combine the items of several MiGeL editions, given oldest first. A
+/// position number found in several editions keeps only the item of the
+/// newest one; positions dropped from newer editions stay matchable with
+/// their last edition. Items of the newest edition come first.
+pub fn merge_editions(editions: Vec<Vec<MigelItem>>) -> Vec<MigelItem> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut merged = Vec::new();
+    for items in editions.into_iter().rev() {
+        for item in items {
+            if seen.insert(item.position_nr.clone()) {
+                merged.push(item);
+            }
+        }
+    }
+    merged
+}
+
+/// Keyword → indices of the MiGeL items using it. Ordered, so every walk
+/// over the index (candidate search, export) is the same from run to run.
+pub type KeywordIndex = BTreeMap<String, Vec<usize>>;
+
 /// Build an inverted index: keyword → list of MigelItem indices.
 /// Uses all_keywords (DE+FR+IT) for broad candidate finding.
-pub fn build_keyword_index(items: &[MigelItem]) -> HashMap<String, Vec<usize>> {
-    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+pub fn build_keyword_index(items: &[MigelItem]) -> KeywordIndex {
+    let mut index = KeywordIndex::new();
     for (i, item) in items.iter().enumerate() {
         for kw in &item.all_keywords {
             index.entry(kw.clone()).or_default().push(i);
@@ -255,6 +912,21 @@ pub fn build_keyword_index(items: &[MigelItem]) -> HashMap<String, Vec<usize>> {
     index
 }
 
+/// The keyword index resolved to position numbers, sorted by keyword (for
+/// `--export-keywords`).
+pub fn keyword_postings<'a>(
+    items: &'a [MigelItem],
+    keyword_index: &'a KeywordIndex,
+) -> BTreeMap<&'a str, Vec<&'a str>> {
+    keyword_index
+        .iter()
+        .map(|(kw, idxs)| {
+            let positions = idxs.iter().map(|&i| items[i].position_nr.as_str()).collect();
+            (kw.as_str(), positions)
+        })
+        .collect()
+}
+
 /// Split text into words (split on non-alphanumeric characters).
 fn split_words(text: &str) -> Vec<&str> {
     text.split(|c: char| !c.is_alphanumeric())
@@ -262,14 +934,53 @@ fn split_words(text: &str) -> Vec<&str> {
         .collect()
 }
 
+/// A CSV field with embedded newlines (a quoted multi-line description) on
+/// one line, for console output. Matching always reads every line.
+pub fn single_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Mark the words of `text` that match one of `keywords` (same word rules
+/// as scoring; `german` enables compound and fuzzy matching, `boundary` is
+/// `MatchOptions::word_boundary`) as `[word]`. The lines of a multi-line
+/// field are joined, so a word after a line break is marked on its own.
+pub fn highlight_keywords(text: &str, keywords: &[String], german: bool, boundary: bool) -> String {
+    text.split_whitespace()
+        .map(|token| {
+            let normalized = normalize_for_match(token);
+            let words = split_words(&normalized);
+            if keywords.iter().any(|kw| word_match(&words, kw, german, german, boundary)) {
+                format!("[{}]", token)
+            } else {
+                token.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// Check if a keyword matches in the text at word level.
 /// - `suffix`: if true, also matches as a suffix of a compound word
 ///   (e.g., "katheter" in "verweilkatheter"). Only for German.
 /// - `fuzzy`: if true, also tries keyword truncated by 1 char (German plural/case).
 ///   Only for German.
+/// - `boundary` (`--word-boundary`): suffix matching only for keywords of at
+///   least `COMPOUND_MIN_LEN` chars, so "band" no longer matches "verband".
+///
 /// FR/IT should use suffix=false, fuzzy=false to prevent cross-type matches
 /// (e.g., "prothese" in "endoprothese" matching eye prosthesis).
-fn word_match(text_words: &[&str], keyword: &str, suffix: bool, fuzzy: bool) -> bool {
+fn word_match(
+    text_words: &[&str],
+    keyword: &str,
+    suffix: bool,
+    fuzzy: bool,
+    boundary: bool,
+) -> bool {
+    let suffix = suffix && (!boundary || keyword.len() >= COMPOUND_MIN_LEN);
     for word in text_words {
         // Exact word match
         if *word == keyword {
@@ -294,26 +1005,123 @@ fn word_match(text_words: &[&str], keyword: &str, suffix: bool, fuzzy: bool) ->
     false
 }
 
-/// Check if keyword matches anywhere in text as a substring (for candidate pre-filter).
-/// Uses fuzzy suffix matching for keywords >= 7 chars.
-fn fuzzy_contains(haystack: &str, keyword: &str) -> bool {
-    if haystack.contains(keyword) {
-        return true;
-    }
-    if keyword.len() >= 7 {
-        let trunc = &keyword[..keyword.len() - 1];
-        if haystack.contains(trunc) {
-            return true;
+/// Minimum keyword length for the compound-aware candidate step. Shorter
+/// keywords only find candidates through exact token lookups.
+///
+/// An item can only pass the match thresholds with a matched keyword of at
+/// least 6 chars, so substring checks for shorter keywords never produce a
+/// match that the token lookup would miss.
+pub const COMPOUND_MIN_LEN: usize = 6;
+
+/// Find candidate item indices for the given product tokens.
+/// - Exact token lookups in the ordered index (O(log n) per token)
+/// - Compound-aware step: every substring of `COMPOUND_MIN_LEN` or more chars
+///   of a token is looked up as a keyword (e.g. "katheter" in
+///   "blasenkatheter"), and as a keyword without its last char (the fuzzy
+///   variant, "orthese" for "orthesen"). The cost depends on the token
+///   lengths, not on the size of the index.
+fn find_candidates(
+    tokens: &[&str],
+    keyword_index: &KeywordIndex,
+) -> BTreeSet<usize> {
+    let mut candidates: BTreeSet<usize> = BTreeSet::new();
+    for token in tokens {
+        if let Some(indices) = keyword_index.get(*token) {
+            candidates.extend(indices);
+        }
+        let bounds: Vec<usize> = token
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([token.len()])
+            .collect();
+        for (n, &start) in bounds.iter().enumerate() {
+            for &end in bounds[n + 1..].iter().filter(|&&end| end - start >= COMPOUND_MIN_LEN) {
+                let part = &token[start..end];
+                if let Some(indices) = keyword_index.get(part) {
+                    candidates.extend(indices);
+                }
+                // Keywords of 7+ chars also match without their last char
+                let longer = keyword_index
+                    .range::<str, _>((Bound::Excluded(part), Bound::Unbounded))
+                    .take_while(|(keyword, _)| keyword.starts_with(part))
+                    .filter(|(keyword, _)| keyword.len() == part.len() + 1);
+                for (_, indices) in longer {
+                    candidates.extend(indices);
+                }
+            }
         }
     }
-    false
+    candidates
+}
+
+/// Minimum word length for phonetic matching; shorter words share their
+/// codes with too many unrelated words.
+const PHONETIC_MIN_LEN: usize = 5;
+
+/// Share of a keyword's length a phonetic-only match contributes to the
+/// score (an exact or compound match contributes all of it).
+const PHONETIC_WEIGHT: f64 = 0.5;
+
+/// Kölner Phonetik codes of the words of `PHONETIC_MIN_LEN` or more chars.
+fn phonetic_codes<S: AsRef<str>>(words: &[S]) -> Vec<String> {
+    words
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|w| w.len() >= PHONETIC_MIN_LEN)
+        .map(cologne_code)
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+/// The keyword sounds like one of the product words (`text_codes`).
+fn phonetic_match(keyword: &str, text_codes: &HashSet<String>) -> bool {
+    keyword.len() >= PHONETIC_MIN_LEN && text_codes.contains(&cologne_code(keyword))
+}
+
+/// DE `keyword_score` for `--phonetic`: keywords that `word_match` misses
+/// but that share their Kölner Phonetik code with a product word count at
+/// `PHONETIC_WEIGHT` of their length ("Kompresse" for "Compresse"). The
+/// maximum length and the count include them.
+fn phonetic_keyword_score(
+    text_words: &[&str],
+    text_codes: &HashSet<String>,
+    keywords: &[String],
+    boundary: bool,
+) -> (f64, usize, usize) {
+    let total: f64 = keywords.iter().map(|k| k.len() as f64).sum();
+    if total == 0.0 {
+        return (0.0, 0, 0);
+    }
+    let mut matched_weight = 0.0;
+    let mut max_matched_len = 0;
+    let mut matched_count = 0;
+    for kw in keywords {
+        let weight = if word_match(text_words, kw, true, true, boundary) {
+            1.0
+        } else if phonetic_match(kw, text_codes) {
+            PHONETIC_WEIGHT
+        } else {
+            continue;
+        };
+        matched_weight += kw.len() as f64 * weight;
+        matched_count += 1;
+        max_matched_len = max_matched_len.max(kw.len());
+    }
+    (matched_weight / total, max_matched_len, matched_count)
 }
 
 /// Compute keyword overlap score using word-level matching.
 /// Returns (score, max_matched_keyword_len, matched_count).
 /// `suffix`: allow compound word suffix matching (German only)
 /// `fuzzy`: allow truncated keyword matching (German only)
-fn keyword_score(text_words: &[&str], keywords: &[String], suffix: bool, fuzzy: bool) -> (f64, usize, usize) {
+/// `boundary`: restrict suffix matching to long keywords (see `word_match`)
+fn keyword_score(
+    text_words: &[&str],
+    keywords: &[String],
+    suffix: bool,
+    fuzzy: bool,
+    boundary: bool,
+) -> (f64, usize, usize) {
     let total: f64 = keywords.iter().map(|k| k.len() as f64).sum();
     if total == 0.0 {
         return (0.0, 0, 0);
@@ -322,7 +1130,7 @@ fn keyword_score(text_words: &[&str], keywords: &[String], suffix: bool, fuzzy:
     let mut max_matched_len = 0;
     let mut matched_count = 0;
     for kw in keywords {
-        if word_match(text_words, kw, suffix, fuzzy) {
+        if word_match(text_words, kw, suffix, fuzzy, boundary) {
             matched_weight += kw.len() as f64;
             matched_count += 1;
             if kw.len() > max_matched_len {
@@ -333,6 +1141,49 @@ fn keyword_score(text_words: &[&str], keywords: &[String], suffix: bool, fuzzy:
     (matched_weight / total, max_matched_len, matched_count)
 }
 
+/// DE score `own` (`keyword_score` of `keywords`) including the item's
+/// category context. Once an own
+/// keyword matched, each matched category keyword adds `weight` times its
+/// length to the matched and the total weight, so category terms pull the
+/// score up without diluting items in large categories. The maximum length
+/// and the count stay those of the own keywords: category terms never decide
+/// the thresholds on their own.
+fn category_weighted_score(
+    text_words: &[&str],
+    keywords: &[String],
+    own: (f64, usize, usize),
+    category: &[String],
+    weight: f64,
+    boundary: bool,
+) -> (f64, usize, usize) {
+    let (score, max_len, count) = own;
+    if count == 0 {
+        return (score, max_len, count);
+    }
+    let own_total: f64 = keywords.iter().map(|k| k.len() as f64).sum();
+    let bonus: f64 = category
+        .iter()
+        .filter(|kw| word_match(text_words, kw, true, true, boundary))
+        .map(|kw| kw.len() as f64 * weight)
+        .sum();
+    ((score * own_total + bonus) / (own_total + bonus), max_len, count)
+}
+
+/// List the keywords that match in the text (same rules as `keyword_score`).
+fn matching_keywords(
+    text_words: &[&str],
+    keywords: &[String],
+    suffix: bool,
+    fuzzy: bool,
+    boundary: bool,
+) -> Vec<String> {
+    keywords
+        .iter()
+        .filter(|kw| word_match(text_words, kw, suffix, fuzzy, boundary))
+        .cloned()
+        .collect()
+}
+
 /// Find the best-matching MiGeL item for a product.
 /// CRITICAL: Each language's keywords are scored ONLY against the same language's
 /// product description. This prevents cross-language false positives (e.g.,
@@ -343,12 +1194,32 @@ pub fn find_best_migel_match<'a>(
     desc_it: &str,
     brand: &str,
     migel_items: &'a [MigelItem],
-    keyword_index: &HashMap<String, Vec<usize>>,
-) -> Option<&'a MigelItem> {
-    let de_lower = normalize_german(&format!("{} {}", desc_de, brand)).to_lowercase();
-    let fr_lower = normalize_german(&format!("{} {}", desc_fr, brand)).to_lowercase();
-    let it_lower = normalize_german(&format!("{} {}", desc_it, brand)).to_lowercase();
-    // Combined text only for candidate finding (broad pre-filter)
+    keyword_index: &KeywordIndex,
+    options: &MatchOptions,
+) -> Option<MigelMatch<'a>> {
+    rank_migel_matches(desc_de, desc_fr, desc_it, brand, migel_items, keyword_index, options, 1)
+        .into_iter()
+        .next()
+}
+
+/// Rank all MiGeL items that pass the match criteria for a product, best first
+/// (by score, then longest matched keyword), keeping at most `limit` matches.
+#[allow(clippy::too_many_arguments)]
+pub fn rank_migel_matches<'a>(
+    desc_de: &str,
+    desc_fr: &str,
+    desc_it: &str,
+    brand: &str,
+    migel_items: &'a [MigelItem],
+    keyword_index: &KeywordIndex,
+    options: &MatchOptions,
+    limit: usize,
+) -> Vec<MigelMatch<'a>> {
+    let (synonyms, abbreviations) = (&options.synonyms, &options.abbreviations);
+    let de_lower = synonyms.expand(abbreviations.expand(&format!("{} {}", desc_de, brand)));
+    let fr_lower = synonyms.expand(abbreviations.expand(&format!("{} {}", desc_fr, brand)));
+    let it_lower = synonyms.expand(abbreviations.expand(&format!("{} {}", desc_it, brand)));
+    // Combined text only for candidate finding
     let combined = format!("{} {} {}", de_lower, fr_lower, it_lower);
 
     // Pre-split text into words for word-level matching in scoring
@@ -356,44 +1227,75 @@ pub fn find_best_migel_match<'a>(
     let fr_words = split_words(&fr_lower);
     let it_words = split_words(&it_lower);
 
-    // Step 1: Find candidate items via the broad keyword index (substring matching OK here)
-    let mut candidates: HashMap<usize, bool> = HashMap::new();
-    for (keyword, indices) in keyword_index {
-        if fuzzy_contains(&combined, keyword) {
-            for &idx in indices {
-                candidates.insert(idx, true);
-            }
-        }
+    // Step 1: Find candidate items via token lookups in the keyword index,
+    // plus a compound-aware substring pass for long keywords
+    let mut candidates = find_candidates(&split_words(&combined), keyword_index);
+    // --phonetic: items with a DE keyword that sounds like a DE product word
+    let de_codes: HashSet<String> = if options.phonetic {
+        phonetic_codes(&de_words).into_iter().collect()
+    } else {
+        HashSet::new()
+    };
+    if !de_codes.is_empty() {
+        candidates.extend(migel_items.iter().enumerate().filter_map(|(idx, item)| {
+            item.phonetic_de
+                .iter()
+                .any(|code| de_codes.contains(code))
+                .then_some(idx)
+        }));
     }
 
     // Step 2: Score each candidate using WORD-LEVEL matching against per-language text
     // DE uses fuzzy word matching (handles German plural/case: Orthese/Orthesen)
     // FR/IT use exact word matching only
     // Secondary keywords from additional lines count as bonus matches
-    candidates
-        .keys()
+    let mut passing: Vec<(usize, f64, usize, usize, usize)> = candidates
+        .iter()
         .filter_map(|&idx| {
             let item = &migel_items[idx];
             // Primary scores (first-line keywords)
-            let (score_de, max_len_de, count_de) = keyword_score(&de_words, &item.keywords_de, true, true);
-            let (score_fr, max_len_fr, count_fr) = keyword_score(&fr_words, &item.keywords_fr, false, false);
-            let (score_it, max_len_it, count_it) = keyword_score(&it_words, &item.keywords_it, false, false);
+            let own_de = if options.phonetic {
+                phonetic_keyword_score(
+                    &de_words,
+                    &de_codes,
+                    &item.keywords_de,
+                    options.word_boundary,
+                )
+            } else {
+                keyword_score(&de_words, &item.keywords_de, true, true, options.word_boundary)
+            };
+            let (score_de, max_len_de, count_de) = if options.category_weight > 0.0 {
+                category_weighted_score(
+                    &de_words,
+                    &item.keywords_de,
+                    own_de,
+                    &item.category_de,
+                    options.category_weight,
+                    options.word_boundary,
+                )
+            } else {
+                own_de
+            };
+            let (score_fr, max_len_fr, count_fr) =
+                keyword_score(&fr_words, &item.keywords_fr, false, false, false);
+            let (score_it, max_len_it, count_it) =
+                keyword_score(&it_words, &item.keywords_it, false, false, false);
 
             // Secondary bonus matches: only count if at least 1 primary keyword matched
             // This prevents secondary-only matches (e.g., "Verlängerung" from MiGeL line 2
             // matching unrelated products that happen to have "Verlängerung")
             let (_, sec_max_de, sec_count_de) = if count_de > 0 {
-                keyword_score(&de_words, &item.secondary_de, true, true)
+                keyword_score(&de_words, &item.secondary_de, true, true, options.word_boundary)
             } else {
                 (0.0, 0, 0)
             };
             let (_, sec_max_fr, sec_count_fr) = if count_fr > 0 {
-                keyword_score(&fr_words, &item.secondary_fr, false, false)
+                keyword_score(&fr_words, &item.secondary_fr, false, false, false)
             } else {
                 (0.0, 0, 0)
             };
             let (_, sec_max_it, sec_count_it) = if count_it > 0 {
-                keyword_score(&it_words, &item.secondary_it, false, false)
+                keyword_score(&it_words, &item.secondary_it, false, false, false)
             } else {
                 (0.0, 0, 0)
             };
@@ -407,35 +1309,359 @@ pub fn find_best_migel_match<'a>(
             let max_it = max_len_it.max(sec_max_it);
 
             // Pick the best-scoring language (by primary score, using total count for threshold)
-            let (best_score, best_max_len, best_count) = [
+            let (best_lang, (best_score, best_max_len, best_count)) = [
                 (score_de, max_de, total_de),
                 (score_fr, max_fr, total_fr),
                 (score_it, max_it, total_it),
             ]
                 .iter()
                 .copied()
-                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
-                .unwrap_or((0.0, 0, 0));
+                .enumerate()
+                .max_by(|a, b| a.1 .0.partial_cmp(&b.1 .0).unwrap_or(std::cmp::Ordering::Equal))
+                .unwrap_or((0, (0.0, 0, 0)));
 
             // Match criteria:
-            // - 2+ matched keywords (primary+secondary): score >= 0.3, max keyword len >= 6
-            // - 1 matched keyword: score >= 0.5, keyword len >= 10
+            // - 2+ matched keywords (primary+secondary): score >= min_score (0.3),
+            //   max keyword len >= 6
+            // - 1 matched keyword: score >= 0.5 (or min_score if higher), keyword len >= 10
+            // - always at least `min_matches` matched keywords
             let passes = if best_count >= 2 {
-                best_score >= 0.3 && best_max_len >= 6
+                best_score >= options.min_score && best_max_len >= 6
             } else {
-                best_score >= 0.5 && best_max_len >= 10
+                best_score >= options.min_score.max(0.5) && best_max_len >= 10
             };
 
-            if passes {
-                Some((idx, best_score, best_max_len))
+            if passes && best_count >= options.min_matches {
+                Some((idx, best_score, best_max_len, best_count, best_lang))
             } else {
                 None
             }
         })
-        .max_by(|a, b| {
-            a.1.partial_cmp(&b.1)
-                .unwrap_or(std::cmp::Ordering::Equal)
-                .then(a.2.cmp(&b.2))
+        .collect();
+
+    passing.sort_by(|a, b| {
+        let (item_a, item_b) = (&migel_items[a.0], &migel_items[b.0]);
+        let tie = match options.tie_break {
+            TieBreak::Length => b.2.cmp(&a.2),
+            TieBreak::Count => b.3.cmp(&a.3),
+            TieBreak::ShortestDesc => item_a.bezeichnung.len().cmp(&item_b.bezeichnung.len()),
+            TieBreak::Position => std::cmp::Ordering::Equal,
+        };
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(tie)
+            .then_with(|| item_a.position_nr.cmp(&item_b.position_nr))
+    });
+    passing.truncate(limit);
+
+    passing
+        .into_iter()
+        .map(|(idx, score, _, _, lang)| {
+            let item = &migel_items[idx];
+            // Provenance for the winning language only (same rules as scoring)
+            let (words, primary, secondary, language) = match lang {
+                0 => (&de_words, &item.keywords_de, &item.secondary_de, Language::De),
+                1 => (&fr_words, &item.keywords_fr, &item.secondary_fr, Language::Fr),
+                _ => (&it_words, &item.keywords_it, &item.secondary_it, Language::It),
+            };
+            let german = language == Language::De;
+            let boundary = options.word_boundary;
+            let mut matched_keywords = matching_keywords(words, primary, german, german, boundary);
+            if german && options.phonetic {
+                let phonetic: Vec<String> = primary
+                    .iter()
+                    .filter(|kw| !matched_keywords.contains(kw) && phonetic_match(kw, &de_codes))
+                    .cloned()
+                    .collect();
+                matched_keywords.extend(phonetic);
+            }
+            if !matched_keywords.is_empty() {
+                matched_keywords.extend(matching_keywords(
+                    words, secondary, german, german, boundary,
+                ));
+                if german && options.category_weight > 0.0 {
+                    let category =
+                        matching_keywords(words, &item.category_de, true, true, boundary);
+                    matched_keywords.extend(category);
+                }
+            }
+            MigelMatch {
+                item,
+                score,
+                matched_keywords,
+                language,
+            }
         })
-        .map(|(idx, _, _)| &migel_items[idx])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An item with the keywords `parse_migel_csv` extracts from `bezeichnung`.
+    fn item(position_nr: &str, bezeichnung: &str) -> MigelItem {
+        MigelItem {
+            position_nr: position_nr.to_string(),
+            bezeichnung: bezeichnung.lines().next().unwrap_or("").trim().to_string(),
+            limitation: String::new(),
+            betrag: None,
+            keywords_de: extract_keywords(bezeichnung),
+            keywords_fr: Vec::new(),
+            keywords_it: Vec::new(),
+            secondary_de: extract_secondary_keywords(bezeichnung),
+            secondary_fr: Vec::new(),
+            secondary_it: Vec::new(),
+            category_de: Vec::new(),
+            all_keywords: extract_keywords_full(bezeichnung),
+            category_fallback: false,
+            keywords_trimmed: false,
+            phonetic_de: Vec::new(),
+            edition: None,
+        }
+    }
+
+    /// The best match of a German product text among `items`.
+    fn best_match<'a>(
+        desc_de: &str,
+        items: &'a [MigelItem],
+        options: &MatchOptions,
+    ) -> Option<MigelMatch<'a>> {
+        let index = build_keyword_index(items);
+        find_best_migel_match(desc_de, "", "", "", items, &index, options)
+    }
+
+    /// An index with item `i` under the `i`-th keyword.
+    fn index_of(keywords: &[&str]) -> KeywordIndex {
+        keywords
+            .iter()
+            .enumerate()
+            .map(|(i, kw)| (kw.to_string(), vec![i]))
+            .collect()
+    }
+
+    #[test]
+    fn compound_step_finds_keyword_inside_compound() {
+        let index = index_of(&["verband", "katheter"]);
+        let candidates = find_candidates(&["wundverband", "steril"], &index);
+        assert_eq!(candidates, BTreeSet::from([0]));
+    }
+
+    #[test]
+    fn compound_step_skips_short_keywords() {
+        let index = index_of(&["band", "verband"]);
+        assert_eq!(
+            find_candidates(&["wundverband"], &index),
+            BTreeSet::from([1])
+        );
+        assert_eq!(find_candidates(&["band"], &index), BTreeSet::from([0]));
+    }
+
+    /// Check if keyword matches anywhere in text as a substring (the old
+    /// candidate pre-filter). Uses fuzzy suffix matching for keywords >= 7 chars.
+    fn fuzzy_contains(haystack: &str, keyword: &str) -> bool {
+        if haystack.contains(keyword) {
+            return true;
+        }
+        if keyword.len() >= 7 {
+            let trunc = &keyword[..keyword.len() - 1];
+            if haystack.contains(trunc) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The linear scan `find_candidates` replaced: every keyword contained
+    /// in the product text.
+    fn linear_scan(text: &str, keyword_index: &KeywordIndex) -> BTreeSet<usize> {
+        keyword_index
+            .iter()
+            .filter(|(keyword, _)| fuzzy_contains(text, keyword))
+            .flat_map(|(_, indices)| indices.iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn compound_step_keeps_the_long_keywords_of_the_linear_scan() {
+        let index = index_of(&[
+            "band",
+            "verband",
+            "katheter",
+            "kompresse",
+            "orthese",
+            "gaze",
+            "blasenkatheter",
+            "kompressen",
+        ]);
+        for text in [
+            "wundverband steril",
+            "einmal blasenkatheter ch12",
+            "gazekompressen 10x10",
+            "knieorthesen",
+            "band",
+        ] {
+            let tokens = split_words(text);
+            let expected: BTreeSet<usize> = linear_scan(text, &index)
+                .into_iter()
+                .filter(|&i| {
+                    let keyword = index.iter().find(|(_, idx)| idx.contains(&i)).unwrap().0;
+                    keyword.len() >= COMPOUND_MIN_LEN || tokens.contains(&keyword.as_str())
+                })
+                .collect();
+            assert_eq!(find_candidates(&tokens, &index), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn both_sides_normalize_alike() {
+        assert_eq!(normalize_for_match("Absauggeräte"), "absauggeraete");
+        assert_eq!(normalize_for_match("ABSAUGGERÄTE"), "absauggeraete");
+        assert_eq!(normalize_for_match("Élément Straße"), "element strasse");
+        // MiGeL keywords and product words of the same text, in any case
+        let migel = extract_keywords_full("Absauggeräte für Sekrete");
+        let product = Abbreviations::default().expand("ABSAUGGERÄTE FÜR SEKRETE");
+        let product_words: Vec<String> = split_words(&product)
+            .into_iter()
+            .map(str::to_string)
+            .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+            .collect();
+        assert_eq!(migel, ["absauggeraete", "sekrete"]);
+        assert_eq!(product_words, migel);
+    }
+
+    #[test]
+    fn full_description_keywords_include_later_lines() {
+        // "Blasenkatheter" / "mit Ballon, Latex" / "Packung zu zehn Stück"
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/migel_multiline.csv");
+        let options = ParseOptions {
+            full_description: true,
+            ..ParseOptions::default()
+        };
+        let full = parse_migel_csv(path, &options).unwrap();
+        assert_eq!(full[0].bezeichnung, "Blasenkatheter");
+        for keyword in ["ballon", "latex", "packung", "blasenkatheter"] {
+            assert!(
+                full[0].keywords_de.iter().any(|k| k == keyword),
+                "{}",
+                keyword
+            );
+        }
+
+        let first_line = parse_migel_csv(path, &ParseOptions::default()).unwrap();
+        assert_eq!(first_line[0].bezeichnung, "Blasenkatheter");
+        assert_eq!(first_line[0].keywords_de, ["blasenkatheter"]);
+    }
+
+    #[test]
+    fn equal_scores_always_pick_the_same_winner() {
+        let forward = vec![
+            item("35.01.02.00.1", "Gazekompresse"),
+            item("35.01.01.00.1", "Gazekompresse"),
+        ];
+        let backward: Vec<MigelItem> = forward
+            .iter()
+            .rev()
+            .map(|i| item(&i.position_nr, &i.bezeichnung))
+            .collect();
+        for tie_break in [
+            TieBreak::Length,
+            TieBreak::Count,
+            TieBreak::ShortestDesc,
+            TieBreak::Position,
+        ] {
+            let options = MatchOptions {
+                tie_break,
+                ..MatchOptions::default()
+            };
+            for items in [&forward, &backward] {
+                for _ in 0..3 {
+                    let m = best_match("Gazekompresse steril 10x10", items, &options).unwrap();
+                    assert_eq!(m.score, 1.0);
+                    assert_eq!(m.item.position_nr, "35.01.01.00.1");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fold_case_lowercases_without_locale() {
+        assert_eq!(fold_case("KATHETER"), "katheter");
+        assert_eq!(fold_case("ÄÖÜ Straße"), "äöü straße");
+        assert_eq!(fold_case("ÉLÉMENT À Ç"), "élément à ç");
+        assert_eq!(fold_case("GERÄT"), "gerät");
+        // Turkish dotted and dotless capitals fold to a plain i
+        assert_eq!(fold_case("İNFUSION"), "infusion");
+        assert_eq!(fold_case("INFUSION"), "infusion");
+    }
+
+    #[test]
+    fn word_boundary_keeps_short_keywords_out_of_compounds() {
+        let words = ["verband", "steril"];
+        assert!(word_match(&words, "band", true, true, false));
+        assert!(!word_match(&words, "band", true, true, true));
+        // Whole words and long compound heads still match
+        assert!(word_match(&["band"], "band", true, true, true));
+        assert!(word_match(&["wundverband"], "verband", true, true, true));
+        // FR/IT never match inside compounds
+        assert!(!word_match(&words, "band", false, false, false));
+    }
+
+    #[test]
+    fn matching_keeps_the_source_bezeichnung() {
+        let bezeichnung = "Gehstöcke für Kinder, Größe M";
+        let file = format!("fb2sqlite-migel-{}.csv", std::process::id());
+        let path = std::env::temp_dir().join(file);
+        let csv = format!(
+            "position_nr,bezeichnung,limitation\n10.01.01.00.1,\"{}\nÄltere Ausführung\",\n",
+            bezeichnung
+        );
+        std::fs::write(&path, csv).unwrap();
+        let items = parse_migel_csv(&path.to_string_lossy(), &ParseOptions::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let m = best_match("GEHSTOECKE FUER KINDER", &items, &MatchOptions::default()).unwrap();
+        assert_eq!(m.item.bezeichnung.as_bytes(), bezeichnung.as_bytes());
+    }
+
+    #[test]
+    fn missing_enrichment_sheet_only_costs_its_language() {
+        // DE and FR sheets, no IT sheet
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/migel_without_it.xlsx"
+        );
+        let items = parse_migel_items(path, &ParseOptions::default()).unwrap();
+        assert_eq!(items.len(), 6);
+        let katheter = items
+            .iter()
+            .find(|i| i.position_nr == "15.10.01.00.1")
+            .unwrap();
+        assert_eq!(katheter.keywords_de, ["blasenkatheter", "einmalkatheter"]);
+        assert_eq!(
+            katheter.keywords_fr,
+            ["sonde", "unique", "usage", "vesicale"]
+        );
+        assert!(items.iter().all(|i| i.keywords_it.is_empty()));
+    }
+
+    #[test]
+    fn multi_line_csv_field_is_shown_on_one_line() {
+        let csv = "gtin,description\n\
+                   07612345000011,\"Einmal-Blasenkatheter\n  Nelaton CH12\n\nsteril\"\n";
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let record = reader.records().next().unwrap().unwrap();
+        let description = &record[1];
+        assert_eq!(description.lines().count(), 4);
+        assert_eq!(
+            single_line(description),
+            "Einmal-Blasenkatheter Nelaton CH12 steril"
+        );
+        // A keyword on a later line is still found and marked
+        let keywords = ["nelaton".to_string()];
+        assert_eq!(
+            highlight_keywords(description, &keywords, true, false),
+            "Einmal-Blasenkatheter [Nelaton] CH12 steril"
+        );
+    }
 }
@@ -0,0 +1,50 @@
+/// Parse a number written in Swiss conventions: apostrophes as thousands
+/// separator (`1'234.50`, also `’`) and a decimal comma (`12,50`). When both
+/// a dot and a comma occur, the dot is taken as thousands separator
+/// (`1.234,50`); a lone dot is the decimal point (`1.234` is 1.234).
+/// Returns `None` for empty or non-numeric input.
+pub fn parse_swiss_number(s: &str) -> Option<f64> {
+    let mut cleaned: String = s
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+    if cleaned.contains(',') {
+        if cleaned.contains('.') {
+            cleaned.retain(|c| c != '.');
+        }
+        cleaned = cleaned.replace(',', ".");
+    }
+    cleaned.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_swiss_separators() {
+        assert_eq!(parse_swiss_number("1'234,50"), Some(1234.5));
+        assert_eq!(parse_swiss_number("1'234.50"), Some(1234.5));
+        assert_eq!(parse_swiss_number("1’234.50"), Some(1234.5));
+        assert_eq!(parse_swiss_number("1.234,50"), Some(1234.5));
+        assert_eq!(parse_swiss_number(" 12,50 "), Some(12.5));
+        assert_eq!(parse_swiss_number("12"), Some(12.0));
+    }
+
+    #[test]
+    fn lone_dot_is_decimal() {
+        assert_eq!(parse_swiss_number("1.234"), Some(1.234));
+    }
+
+    #[test]
+    fn rejects_empty_and_text() {
+        assert_eq!(parse_swiss_number(""), None);
+        assert_eq!(parse_swiss_number("  "), None);
+        assert_eq!(parse_swiss_number("'"), None);
+        assert_eq!(parse_swiss_number("CHF 12"), None);
+    }
+}
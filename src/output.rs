@@ -0,0 +1,52 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::fd::FromRawFd;
+
+/// The original stdout while `--output -` streams the database there (or
+/// `--report-format json` writes the summary there). All
+/// log output (`println!`) goes to stderr from `capture` on, so only the
+/// database bytes reach the pipe.
+pub struct StdoutSink(File);
+
+impl StdoutSink {
+    /// Keep a handle to stdout and point file descriptor 1 at stderr.
+    pub fn capture() -> io::Result<StdoutSink> {
+        io::stdout().flush()?;
+        // SAFETY: dup/dup2 on the standard descriptors; the duplicate is a
+        // fresh descriptor owned by the returned File only.
+        unsafe {
+            let fd = libc::dup(libc::STDOUT_FILENO);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+                let err = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(err);
+            }
+            Ok(StdoutSink(File::from_raw_fd(fd)))
+        }
+    }
+
+    /// Copy the finished database file to stdout and remove it.
+    pub fn stream_file(mut self, path: &str) -> io::Result<u64> {
+        let bytes = io::copy(&mut File::open(path)?, &mut self.0)?;
+        self.0.flush()?;
+        fs::remove_file(path)?;
+        Ok(bytes)
+    }
+
+    /// Write `bytes` (the `--report-format json` summary) to stdout.
+    pub fn write_bytes(mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.write_all(bytes)?;
+        self.0.flush()
+    }
+}
+
+/// Temporary database file for `--output -`, unique per process.
+pub fn temp_db_path() -> String {
+    std::env::temp_dir()
+        .join(format!("fb2sqlite-{}.db", std::process::id()))
+        .to_string_lossy()
+        .into_owned()
+}
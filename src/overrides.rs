@@ -0,0 +1,109 @@
+use crate::error::Fb2SqliteError;
+use crate::identifiers::gtin_key;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+/// A reviewed product/MiGeL pair.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Decision {
+    /// The match is correct and is not asked about again
+    Accept,
+    /// The product must not get this MiGeL item
+    Reject,
+}
+
+impl Decision {
+    fn as_str(self) -> &'static str {
+        match self {
+            Decision::Accept => "accept",
+            Decision::Reject => "reject",
+        }
+    }
+}
+
+/// `--overrides`: curated decisions from `--review`, a CSV of
+/// `gtin,position_nr,decision` lines. Decisions are appended as they are
+/// made, so a later line for the same pair replaces an earlier one.
+#[derive(Default)]
+pub struct Overrides {
+    /// GTIN key -> (position number, decision)
+    decisions: HashMap<String, Vec<(String, Decision)>>,
+}
+
+impl Overrides {
+    /// Read an overrides file; a missing file is an empty set (first review).
+    pub fn load(path: &str) -> Result<Overrides, Box<dyn Error>> {
+        let mut overrides = Overrides::default();
+        if !Path::new(path).exists() {
+            return Ok(overrides);
+        }
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+        for (line_nr, result) in reader.records().enumerate() {
+            let record = result?;
+            let field = |i| record.get(i).unwrap_or("").trim();
+            let decision = match field(2) {
+                "accept" => Decision::Accept,
+                "reject" => Decision::Reject,
+                other => {
+                    return Err(Fb2SqliteError::Parse(format!(
+                        "{}:{}: expected decision accept or reject, got \"{}\"",
+                        path,
+                        line_nr + 2,
+                        other
+                    ))
+                    .into())
+                }
+            };
+            overrides.insert(field(0), field(1), decision);
+        }
+        Ok(overrides)
+    }
+
+    fn insert(&mut self, gtin: &str, position_nr: &str, decision: Decision) {
+        let entries = self.decisions.entry(gtin_key(gtin)).or_default();
+        entries.retain(|(position, _)| position != position_nr);
+        entries.push((position_nr.to_string(), decision));
+    }
+
+    /// The decision recorded for this product and MiGeL item.
+    pub fn decision(&self, gtin: &str, position_nr: &str) -> Option<Decision> {
+        self.decisions
+            .get(&gtin_key(gtin))?
+            .iter()
+            .find(|(position, _)| position == position_nr)
+            .map(|(_, decision)| *decision)
+    }
+
+    /// Number of MiGeL items rejected for this product.
+    pub fn rejected_count(&self, gtin: &str) -> usize {
+        self.decisions.get(&gtin_key(gtin)).map_or(0, |entries| {
+            entries
+                .iter()
+                .filter(|(_, decision)| *decision == Decision::Reject)
+                .count()
+        })
+    }
+
+    /// Record a decision and append it to the overrides file at `path`
+    /// (created with a header row if missing).
+    pub fn record(
+        &mut self,
+        path: &str,
+        gtin: &str,
+        position_nr: &str,
+        decision: Decision,
+    ) -> Result<(), Box<dyn Error>> {
+        let new_file = fs::metadata(path).map_or(true, |m| m.len() == 0);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = csv::Writer::from_writer(file);
+        if new_file {
+            writer.write_record(["gtin", "position_nr", "decision"])?;
+        }
+        writer.write_record([gtin, position_nr, decision.as_str()])?;
+        writer.flush()?;
+        self.insert(gtin, position_nr, decision);
+        Ok(())
+    }
+}
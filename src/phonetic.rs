@@ -0,0 +1,80 @@
+/// Kölner Phonetik (Cologne phonetics) code of a word normalized by
+/// `migel::normalize_for_match` (lowercase, umlauts as `ae`/`oe`/`ue`).
+/// Words that sound alike in German share a code, e.g. "Meier", "Mayer" and
+/// "Maier" (67) or "Kompresse" and "Compresse" (46178). Characters other
+/// than `a`-`z` are ignored; a word without letters yields an empty code.
+pub fn cologne_code(word: &str) -> String {
+    let letters: Vec<u8> = word.bytes().filter(u8::is_ascii_lowercase).collect();
+    let mut digits: Vec<u8> = Vec::with_capacity(letters.len() + 1);
+    for (i, &c) in letters.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| letters[p]);
+        let next = letters.get(i + 1).copied();
+        let next_in = |set: &[u8]| next.is_some_and(|n| set.contains(&n));
+        let prev_in = |set: &[u8]| prev.is_some_and(|p| set.contains(&p));
+        match c {
+            b'a' | b'e' | b'i' | b'j' | b'o' | b'u' | b'y' => digits.push(b'0'),
+            b'h' => {}
+            b'b' => digits.push(b'1'),
+            b'p' => digits.push(if next == Some(b'h') { b'3' } else { b'1' }),
+            b'd' | b't' => digits.push(if next_in(b"csz") { b'8' } else { b'2' }),
+            b'f' | b'v' | b'w' => digits.push(b'3'),
+            b'g' | b'k' | b'q' => digits.push(b'4'),
+            b'c' => {
+                let hard = if i == 0 {
+                    next_in(b"ahkloqrux")
+                } else {
+                    next_in(b"ahkoqux") && !prev_in(b"sz")
+                };
+                digits.push(if hard { b'4' } else { b'8' });
+            }
+            b'x' => {
+                if !prev_in(b"ckq") {
+                    digits.push(b'4');
+                }
+                digits.push(b'8');
+            }
+            b'l' => digits.push(b'5'),
+            b'm' | b'n' => digits.push(b'6'),
+            b'r' => digits.push(b'7'),
+            b's' | b'z' => digits.push(b'8'),
+            _ => {}
+        }
+    }
+    digits.dedup();
+    // Vowels only count at the start of the word
+    digits
+        .iter()
+        .enumerate()
+        .filter(|&(i, &d)| i == 0 || d != b'0')
+        .map(|(_, &d)| d as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes() {
+        assert_eq!(cologne_code("wikipedia"), "3412");
+        assert_eq!(cologne_code("mueller-luedenscheidt"), "65752682");
+        assert_eq!(cologne_code("breschnew"), "17863");
+    }
+
+    #[test]
+    fn alike_words_share_a_code() {
+        for word in ["meier", "mayer", "maier", "meyer"] {
+            assert_eq!(cologne_code(word), "67", "{}", word);
+        }
+        assert_eq!(cologne_code("kompresse"), "46178");
+        assert_eq!(cologne_code("compresse"), "46178");
+        assert_eq!(cologne_code("katheter"), cologne_code("catheter"));
+    }
+
+    #[test]
+    fn non_letters_are_ignored() {
+        assert_eq!(cologne_code(""), "");
+        assert_eq!(cologne_code("10x10"), "48");
+        assert_eq!(cologne_code("123"), "");
+    }
+}
@@ -0,0 +1,90 @@
+use crate::db::sanitize_column;
+use crate::error::Fb2SqliteError;
+use std::error::Error;
+
+/// `--rename-columns`: new names for output columns, from a file of
+/// `original=new_name` lines. The original is a CSV header or its sanitized
+/// column name (compared case-insensitively, like SQLite does); columns
+/// without an entry keep their sanitized name.
+#[derive(Clone, Default)]
+pub struct ColumnRenames {
+    /// Sanitized original name -> new name, in file order
+    names: Vec<(String, String)>,
+}
+
+impl ColumnRenames {
+    /// Read a rename file; empty lines and `#` comments are skipped. New
+    /// names must be plain SQL identifiers (`[A-Za-z_][A-Za-z0-9_]*`) and
+    /// neither side may be given twice.
+    pub fn load(path: &str) -> Result<ColumnRenames, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let mut renames = ColumnRenames::default();
+        for (line_nr, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse_error = |message: String| {
+                Fb2SqliteError::Parse(format!("{}:{}: {}", path, line_nr + 1, message))
+            };
+            let Some((original, new_name)) = line.split_once('=') else {
+                return Err(parse_error("expected original=new_name".into()).into());
+            };
+            let (original, new_name) = (sanitize_column(original.trim()), new_name.trim());
+            if original.is_empty() {
+                return Err(parse_error("missing the original column name".into()).into());
+            }
+            if !is_identifier(new_name) {
+                return Err(parse_error(format!(
+                    "\"{}\" is not a valid SQL identifier (letters, digits and _)",
+                    new_name
+                ))
+                .into());
+            }
+            if renames.get(&original).is_some() {
+                let message = format!("column \"{}\" is renamed twice", original);
+                return Err(parse_error(message).into());
+            }
+            if renames.get_original(new_name).is_some() {
+                let message = format!("new name \"{}\" is used twice", new_name);
+                return Err(parse_error(message).into());
+            }
+            renames.names.push((original, new_name.to_string()));
+        }
+        Ok(renames)
+    }
+
+    /// The new name of a sanitized column name, if it has one.
+    pub fn get(&self, column: &str) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(original, _)| original.eq_ignore_ascii_case(column))
+            .map(|(_, new_name)| new_name.as_str())
+    }
+
+    /// The original column already renamed to `new_name`, if any.
+    fn get_original(&self, new_name: &str) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(new_name))
+            .map(|(original, _)| original.as_str())
+    }
+
+    /// Originals that name none of the sanitized `columns`.
+    pub fn unused(&self, columns: &[String]) -> Vec<&str> {
+        self.names
+            .iter()
+            .filter(|(original, _)| !columns.iter().any(|c| c.eq_ignore_ascii_case(original)))
+            .map(|(original, _)| original.as_str())
+            .collect()
+    }
+}
+
+/// Whether `name` is a plain SQL identifier (`[A-Za-z_][A-Za-z0-9_]*`).
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
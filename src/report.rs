@@ -0,0 +1,57 @@
+use crate::output::StdoutSink;
+use serde_json::{Map, Value};
+use std::error::Error;
+
+/// `--report-format`: how the end-of-run summary is printed.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// Summary lines on stdout
+    Text,
+    /// One JSON object on stdout; all log output goes to stderr
+    Json,
+    /// No summary
+    None,
+}
+
+/// The end-of-run summary of `import` and `migel`. Each fact is given as a
+/// text line (`line`) and as a JSON field (`set`); the format decides which
+/// of the two is output.
+pub struct Report {
+    format: ReportFormat,
+    fields: Map<String, Value>,
+}
+
+impl Report {
+    pub fn new(format: ReportFormat) -> Report {
+        Report {
+            format,
+            fields: Map::new(),
+        }
+    }
+
+    /// Print a summary line (`text` format only).
+    pub fn line(&self, text: impl AsRef<str>) {
+        if self.format == ReportFormat::Text {
+            println!("{}", text.as_ref());
+        }
+    }
+
+    /// Record a field of the JSON summary.
+    pub fn set(&mut self, key: &str, value: impl Into<Value>) {
+        self.fields.insert(key.to_string(), value.into());
+    }
+
+    /// In `json` format write the summary to the captured stdout, which is
+    /// used up; otherwise hand `stdout` back (it then belongs to `--output -`).
+    pub fn finish(self, stdout: Option<StdoutSink>) -> Result<Option<StdoutSink>, Box<dyn Error>> {
+        match (self.format, stdout) {
+            (ReportFormat::Json, Some(stdout)) => {
+                let mut json = serde_json::to_string_pretty(&Value::Object(self.fields))?;
+                json.push('\n');
+                stdout.write_bytes(json.as_bytes())?;
+                Ok(None)
+            }
+            (_, stdout) => Ok(stdout),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::interrupt;
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+/// Base delay between attempts; attempt n waits n times this long.
+pub const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Run `op` up to `1 + retries` times until it succeeds, with a linear
+/// backoff between attempts. Returns the last error if all attempts fail or
+/// Ctrl-C was pressed.
+pub fn with_retries<T>(
+    label: &str,
+    retries: u32,
+    mut op: impl FnMut() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < retries && !interrupt::interrupted() => {
+                attempt += 1;
+                let delay = RETRY_DELAY * attempt;
+                eprintln!(
+                    "{} failed: {} (retry {}/{} in {}s)",
+                    label,
+                    e,
+                    attempt,
+                    retries,
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
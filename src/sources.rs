@@ -0,0 +1,131 @@
+use crate::error::Fb2SqliteError;
+use csv::ReaderBuilder;
+use std::error::Error;
+use std::io::Cursor;
+
+/// Name of the column that tags merged rows with their feed URL.
+pub const SOURCE_COLUMN: &str = "source";
+
+/// Data rows of one feed and where each of its columns lands in the union.
+struct ParsedFeed<'a> {
+    source: &'a str,
+    positions: Vec<usize>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Merge several decoded CSV feeds into one CSV text. The header is the union
+/// of all column names in first-seen order (a name a feed has twice appears
+/// twice), followed by a `source` column with the feed URL; columns a feed
+/// lacks stay empty. Prints the row count
+/// per source.
+pub fn merge_feeds(
+    feeds: &[(String, String)],
+    skip_bad_rows: bool,
+) -> Result<String, Box<dyn Error>> {
+    let mut columns: Vec<String> = Vec::new();
+    let mut parsed: Vec<ParsedFeed> = Vec::new();
+    for (source, content) in feeds {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(Cursor::new(content));
+        let mut mapping: Option<Vec<usize>> = None;
+        let mut rows = Vec::new();
+        for result in reader.records() {
+            let record = match result {
+                Ok(record) => record,
+                Err(e) if skip_bad_rows => {
+                    eprintln!("Warning: skipping malformed CSV record in {}: {}", source, e);
+                    continue;
+                }
+                Err(e) => return Err(Fb2SqliteError::Parse(format!("{}: {}", source, e)).into()),
+            };
+            if mapping.is_none() {
+                // Header: map each column onto its position in the union; the
+                // k-th column of a name goes to the k-th union column of it,
+                // so duplicated names keep all their values
+                let mut positions: Vec<usize> = Vec::with_capacity(record.len());
+                for name in record.iter() {
+                    let occurrence = record
+                        .iter()
+                        .take(positions.len())
+                        .filter(|n| *n == name)
+                        .count();
+                    let existing = columns
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| *c == name)
+                        .nth(occurrence);
+                    positions.push(match existing {
+                        Some((pos, _)) => pos,
+                        None => {
+                            columns.push(name.to_string());
+                            columns.len() - 1
+                        }
+                    });
+                }
+                mapping = Some(positions);
+            } else {
+                rows.push(record.iter().map(|s| s.to_string()).collect());
+            }
+        }
+        println!("Source {}: {} rows", source, rows.len());
+        parsed.push(ParsedFeed {
+            source,
+            positions: mapping.unwrap_or_default(),
+            rows,
+        });
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut header = columns.clone();
+    header.push(SOURCE_COLUMN.to_string());
+    writer.write_record(&header)?;
+    for feed in &parsed {
+        for row in &feed.rows {
+            let mut merged = vec![String::new(); columns.len()];
+            for (value, &pos) in row.iter().zip(&feed.positions) {
+                merged[pos] = value.clone();
+            }
+            merged.push(feed.source.to_string());
+            writer.write_record(&merged)?;
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(source: &str, content: &str) -> (String, String) {
+        (source.to_string(), content.to_string())
+    }
+
+    #[test]
+    fn duplicated_header_keeps_both_columns() {
+        let feeds = [
+            feed("a", "gtin,Name,Name\n1,Gaze,Kompresse\n"),
+            feed("b", "Name,gtin,preis\n2 Katheter,2,5\n"),
+        ];
+        let merged = merge_feeds(&feeds, false).unwrap();
+        assert_eq!(
+            merged,
+            "gtin,Name,Name,preis,source\n\
+             1,Gaze,Kompresse,,a\n\
+             2,2 Katheter,,5,b\n"
+        );
+    }
+
+    #[test]
+    fn second_feed_adds_its_extra_duplicate() {
+        let feeds = [
+            feed("a", "gtin,Name\n1,Gaze\n"),
+            feed("b", "gtin,Name,Name\n2,Katheter,Ballon\n"),
+        ];
+        let merged = merge_feeds(&feeds, false).unwrap();
+        assert_eq!(
+            merged,
+            "gtin,Name,Name,source\n1,Gaze,,a\n2,Katheter,Ballon,b\n"
+        );
+    }
+}
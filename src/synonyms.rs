@@ -0,0 +1,125 @@
+use crate::error::Fb2SqliteError;
+use crate::migel::{normalize_for_match, COMPOUND_MIN_LEN};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// `--synonyms`: groups of words that name the same thing ("rollstuhl,
+/// fahrstuhl"). Product text is extended with the other members of every
+/// group one of its words belongs to, so a MiGeL keyword matches whichever
+/// synonym the product uses while items keep their own keywords and scores.
+#[derive(Clone, Default)]
+pub struct Synonyms {
+    /// Normalized word -> the other members of its groups
+    members: BTreeMap<String, Vec<String>>,
+}
+
+impl Synonyms {
+    /// Read a file of comma-separated synonym groups, one per line; empty
+    /// lines and `#` comments are skipped. The words are normalized like
+    /// product text; a word in several groups gets the members of all.
+    pub fn load(path: &str) -> Result<Synonyms, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let mut synonyms = Synonyms::default();
+        for (line_nr, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse_error = |message: String| {
+                Fb2SqliteError::Parse(format!("{}:{}: {}", path, line_nr + 1, message))
+            };
+            let mut group: Vec<String> = Vec::new();
+            for entry in line.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let word = normalize_for_match(entry);
+                if !word.chars().all(char::is_alphanumeric) {
+                    return Err(parse_error(format!("synonym \"{}\" is not a single word", entry))
+                        .into());
+                }
+                if !group.contains(&word) {
+                    group.push(word);
+                }
+            }
+            if group.len() < 2 {
+                return Err(parse_error("a synonym group needs at least two words".into()).into());
+            }
+            for word in &group {
+                let others = synonyms.members.entry(word.clone()).or_default();
+                others.extend(group.iter().filter(|w| *w != word).cloned());
+                others.sort();
+                others.dedup();
+            }
+        }
+        Ok(synonyms)
+    }
+
+    /// Append the synonyms of the words of a normalized `text`. A word ending
+    /// in a synonym of `COMPOUND_MIN_LEN` or more chars is a German compound
+    /// and gets the same compound with each other member ("elektrofahrstuhl"
+    /// adds "elektrorollstuhl").
+    pub fn expand(&self, text: String) -> String {
+        if self.members.is_empty() {
+            return text;
+        }
+        let mut extra: Vec<String> = Vec::new();
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            if let Some(others) = self.members.get(word) {
+                extra.extend(others.iter().cloned());
+                continue;
+            }
+            for (member, others) in &self.members {
+                if member.len() >= COMPOUND_MIN_LEN
+                    && word.len() > member.len()
+                    && word.ends_with(member.as_str())
+                {
+                    let stem = &word[..word.len() - member.len()];
+                    extra.extend(others.iter().map(|other| format!("{}{}", stem, other)));
+                }
+            }
+        }
+        if extra.is_empty() {
+            return text;
+        }
+        format!("{} {}", text, extra.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Synonyms loaded from a temporary file with `content`.
+    fn synonyms(name: &str, content: &str) -> Result<Synonyms, Box<dyn Error>> {
+        let file = format!("fb2sqlite-synonyms-{}-{}.txt", name, std::process::id());
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, content)?;
+        let synonyms = Synonyms::load(&path.to_string_lossy());
+        std::fs::remove_file(&path)?;
+        synonyms
+    }
+
+    #[test]
+    fn expand_adds_the_other_members_of_a_group() {
+        let synonyms = synonyms("group", "# mobility\nRollstuhl, Fahrstuhl\n").unwrap();
+        assert_eq!(
+            synonyms.expand("rollstuhl faltbar".into()),
+            "rollstuhl faltbar fahrstuhl"
+        );
+        assert_eq!(synonyms.expand("fahrstuhl".into()), "fahrstuhl rollstuhl");
+        assert_eq!(synonyms.expand("gehstock".into()), "gehstock");
+    }
+
+    #[test]
+    fn expand_replaces_the_head_of_a_compound() {
+        let synonyms = synonyms("compound", "rollstuhl,fahrstuhl\n").unwrap();
+        assert_eq!(
+            synonyms.expand("elektrofahrstuhl".into()),
+            "elektrofahrstuhl elektrorollstuhl"
+        );
+    }
+
+    #[test]
+    fn load_rejects_groups_of_one_and_phrases() {
+        assert!(synonyms("single", "rollstuhl\n").is_err());
+        assert!(synonyms("phrase", "rollstuhl, fahrbarer stuhl\n").is_err());
+    }
+}
@@ -0,0 +1,537 @@
+use crate::error::Fb2SqliteError;
+use crate::retry::with_retries;
+use chrono::Local;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval of the "still uploading" messages when scp shows no progress meter.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Default SCP destination (pillbox.oddb.org web root), see `remote_file`.
+pub const REMOTE_DEST: &str = "zdavatz@65.109.137.20:/var/www/pillbox.oddb.org/";
+
+/// Seconds an idle shared SSH connection stays open; `upload_db` closes it
+/// explicitly once all files are transferred.
+const CONTROL_PERSIST: u32 = 60;
+
+pub struct UploadOptions {
+    /// Additional attempts after a failed transfer (0 = single attempt)
+    pub retries: u32,
+    /// Compare the remote SHA-256 (via `ssh <host> sha256sum`) with the local file
+    pub verify: bool,
+    /// SSH identity file (`-i`)
+    pub ssh_key: Option<String>,
+    /// SSH port (`-P` for scp, `-p` for ssh)
+    pub ssh_port: Option<u16>,
+    /// No progress output: scp runs with `-q` and no heartbeat is printed
+    pub quiet: bool,
+    /// Capture scp's stdout/stderr and log it instead of passing the terminal on
+    pub capture_scp: bool,
+    /// Compare the `data` schema with the deployed database before uploading
+    pub check_schema: bool,
+    /// Upload even if the schema check found differences
+    pub allow_schema_change: bool,
+    /// Share one SSH connection (OpenSSH ControlMaster) between all scp/ssh calls
+    pub multiplex: bool,
+    /// Upload under a date-stamped remote name (`versioned_path`)
+    pub versioned: bool,
+    /// With `versioned`: point the unversioned remote name at the upload
+    pub update_latest: bool,
+    /// Table compared by `check_schema` (`--table-name` when deploying)
+    pub table: String,
+}
+
+impl UploadOptions {
+    /// Connection arguments for `scp` / `ssh`, which differ in the port flag.
+    fn connection_args(&self, port_flag: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(key) = &self.ssh_key {
+            args.push("-i".to_string());
+            args.push(key.clone());
+        }
+        if let Some(port) = self.ssh_port {
+            args.push(port_flag.to_string());
+            args.push(port.to_string());
+        }
+        if self.multiplex {
+            args.push("-o".to_string());
+            args.push("ControlMaster=auto".to_string());
+            args.push("-o".to_string());
+            args.push(format!("ControlPath={}", control_path()));
+            args.push("-o".to_string());
+            args.push(format!("ControlPersist={}", CONTROL_PERSIST));
+        }
+        args
+    }
+}
+
+/// Directory of the shared SSH connection socket. Not the temp dir: a long
+/// `TMPDIR` (macOS `/var/folders/.../T/`) plus the 40 chars of `%C` would
+/// pass the 104-byte limit of a Unix socket path and make ssh fail.
+const CONTROL_DIR: &str = "/tmp";
+
+/// Socket of the shared SSH connection. It is private to this process, so
+/// closing it never cuts off a concurrent run; `%C` (a hash of host, port
+/// and user) keeps connections to different servers apart.
+fn control_path() -> String {
+    format!("{}/fb2sqlite-{}-%C", CONTROL_DIR, std::process::id())
+}
+
+/// Ask the shared SSH connection to `host` to exit. Errors are ignored: the
+/// connection may never have been opened (e.g. the first scp failed).
+fn close_shared_connection(host: &str, options: &UploadOptions) {
+    let _ = Command::new("ssh")
+        .args(options.connection_args("-p"))
+        .args(["-O", "exit", host])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// SCP the `db_filenames` to `remote_dest` and log the total time. With
+/// `UploadOptions.multiplex` the schema checks, transfers, retries and
+/// verifications of all files run over one SSH connection. Several files
+/// need a directory destination (`HOST:DIR/`).
+pub fn upload_db(
+    db_filenames: &[&str],
+    remote_dest: &str,
+    options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    if db_filenames.len() > 1 && !remote_dest.ends_with('/') {
+        return Err(Fb2SqliteError::Validation(format!(
+            "Uploading {} files needs a directory destination ending in /, got {}",
+            db_filenames.len(),
+            remote_dest
+        ))
+        .into());
+    }
+    let start = Instant::now();
+    let result = db_filenames
+        .iter()
+        .try_for_each(|db_filename| upload_file(db_filename, remote_dest, options));
+    if let Some((host, _)) = remote_dest.split_once(':').filter(|_| options.multiplex) {
+        close_shared_connection(host, options);
+    }
+    result?;
+    let elapsed = start.elapsed().as_secs_f64();
+    match db_filenames.len() {
+        1 => println!("Upload finished in {:.1}s", elapsed),
+        count => println!("Uploaded {} files in {:.1}s", count, elapsed),
+    }
+    Ok(())
+}
+
+/// SCP `db_filename` to `remote_dest`, retrying and verifying as configured.
+fn upload_file(
+    db_filename: &str,
+    remote_dest: &str,
+    options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    if options.check_schema {
+        check_remote_schema(db_filename, remote_dest, options)?;
+    }
+    let (host, latest_path) = remote_file(db_filename, remote_dest)?;
+    let remote_path = if options.versioned {
+        versioned_path(&latest_path, &Local::now().format("%d.%m.%Y").to_string())
+    } else {
+        latest_path.clone()
+    };
+    let target = format!("{}:{}", host, remote_path);
+    with_retries("Upload", options.retries, || {
+        println!("Transferring {} to {}...", db_filename, target);
+        let mut command = Command::new("scp");
+        if options.quiet {
+            command.arg("-q");
+        }
+        if options.capture_scp {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let mut child = command
+            .args(options.connection_args("-P"))
+            .arg(db_filename)
+            .arg(&target)
+            .spawn()
+            .map_err(|e| spawn_error("scp", e))?;
+        let stdout = collect_output(child.stdout.take());
+        let stderr = collect_output(child.stderr.take());
+        // scp draws its own progress meter on a terminal; under cron, with
+        // redirected or captured output print a periodic heartbeat instead
+        let heartbeat = !options.quiet && (options.capture_scp || !io::stdout().is_terminal());
+        let status = wait_with_heartbeat(&mut child, db_filename, heartbeat)?;
+        let join = |output: Option<thread::JoinHandle<String>>| output?.join().ok();
+        let (stdout, stderr) = (join(stdout), join(stderr));
+        for line in stdout.iter().flat_map(|text| text.lines()) {
+            println!("[scp] {}", line);
+        }
+        for line in stderr.iter().flat_map(|text| text.lines()) {
+            eprintln!("[scp] {}", line);
+        }
+        if !status.success() {
+            let detail = stderr.as_deref().map(str::trim).filter(|e| !e.is_empty());
+            return Err(Fb2SqliteError::Upload(format!(
+                "SCP failed with exit code: {:?}{}",
+                status.code(),
+                detail.map_or(String::new(), |e| format!(": {}", e))
+            ))
+            .into());
+        }
+        println!("SCP transfer complete.");
+
+        if options.verify {
+            verify_upload(db_filename, &host, &remote_path, options)?;
+        }
+        Ok(())
+    })?;
+    if options.update_latest {
+        update_latest_link(&host, &remote_path, &latest_path, options)?;
+    }
+    Ok(())
+}
+
+/// `--remote-versioned`: the remote path with `_<stamp>` inserted before the
+/// extension of the file name, e.g. `/srv/firstbase.db` becomes
+/// `/srv/firstbase_14.10.2026.db` (the scheme of the dated migel files).
+fn versioned_path(remote_path: &str, stamp: &str) -> String {
+    let name_start = remote_path.rfind('/').map_or(0, |i| i + 1);
+    match remote_path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let (stem, extension) = remote_path.split_at(name_start + dot);
+            format!("{}_{}{}", stem, stamp, extension)
+        }
+        _ => format!("{}_{}", remote_path, stamp),
+    }
+}
+
+/// Quote `text` as one word for the remote shell that runs ssh commands.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// `--update-latest-link`: make `latest_path` a symlink to the versioned
+/// upload. The link is created under a temporary name and renamed over the
+/// old one (`mv -T`), so readers of the unversioned name always find either
+/// the previous or the new database, never a missing file.
+fn update_latest_link(
+    host: &str,
+    versioned_path: &str,
+    latest_path: &str,
+    options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    // A relative target keeps the link valid if the directory is moved
+    let target = Path::new(versioned_path)
+        .file_name()
+        .map_or(versioned_path.into(), |name| name.to_string_lossy());
+    let temp_link = format!("{}.link.tmp", latest_path);
+    let script = format!(
+        "ln -sfn {} {} && mv -fT {} {}",
+        shell_quote(&target),
+        shell_quote(&temp_link),
+        shell_quote(&temp_link),
+        shell_quote(latest_path)
+    );
+    let output = Command::new("ssh")
+        .args(options.connection_args("-p"))
+        .arg(host)
+        .arg(script)
+        .output()
+        .map_err(|e| spawn_error("ssh", e))?;
+    if !output.status.success() {
+        return Err(Fb2SqliteError::Upload(format!(
+            "Updating the link {} failed: {}",
+            latest_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    println!("Linked {} -> {}", latest_path, target);
+    Ok(())
+}
+
+/// `--quiet-scp`: read a piped output of the child to the end in a thread,
+/// so a full pipe cannot stall scp while `wait_with_heartbeat` polls it.
+/// `None` for an inherited (unpiped) stream.
+fn collect_output(pipe: Option<impl Read + Send + 'static>) -> Option<thread::JoinHandle<String>> {
+    pipe.map(|mut pipe| {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let _ = pipe.read_to_end(&mut bytes);
+            String::from_utf8_lossy(&bytes).into_owned()
+        })
+    })
+}
+
+/// Wait for the transfer, printing the elapsed time every
+/// `HEARTBEAT_INTERVAL` when `heartbeat` is set.
+fn wait_with_heartbeat(
+    child: &mut Child,
+    db_filename: &str,
+    heartbeat: bool,
+) -> io::Result<ExitStatus> {
+    if !heartbeat {
+        return child.wait();
+    }
+    let size = fs::metadata(db_filename).map(|m| m.len()).unwrap_or(0);
+    let start = Instant::now();
+    let mut next = HEARTBEAT_INTERVAL;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if start.elapsed() >= next {
+            println!(
+                "Still uploading {} ({:.1} MB), {}s elapsed...",
+                db_filename,
+                size as f64 / 1_048_576.0,
+                next.as_secs()
+            );
+            next += HEARTBEAT_INTERVAL;
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/// How to get `scp` and `ssh` where they are missing.
+pub const OPENSSH_HINT: &str = "install the OpenSSH client (e.g. `apt install openssh-client`)";
+
+/// Path of `program` in the first PATH directory containing it, as the
+/// upload would run it (`doctor` reports it).
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Turn a failure to start `program` into a readable error; a missing
+/// binary (common in slim containers) otherwise surfaces as a bare IO error.
+fn spawn_error(program: &str, e: io::Error) -> Box<dyn Error> {
+    if e.kind() == io::ErrorKind::NotFound {
+        Fb2SqliteError::Upload(format!("{} not found on PATH; {}", program, OPENSSH_HINT)).into()
+    } else {
+        Fb2SqliteError::Upload(format!("Failed to run {}: {}", program, e)).into()
+    }
+}
+
+/// Host and path of the uploaded file on the remote side. A destination
+/// ending in `/` is a directory that receives the file under its local name;
+/// anything else is the full remote file name. scp gets the resolved path
+/// either way, so it never has to guess whether the target is a directory.
+fn remote_file(db_filename: &str, remote_dest: &str) -> Result<(String, String), Box<dyn Error>> {
+    let (host, remote_path) = remote_dest
+        .split_once(':')
+        .filter(|(host, path)| !host.is_empty() && !path.is_empty())
+        .ok_or_else(|| {
+            Fb2SqliteError::Validation(format!(
+                "Upload destination {} is not HOST:PATH",
+                remote_dest
+            ))
+        })?;
+    if !remote_path.ends_with('/') {
+        return Ok((host.to_string(), remote_path.to_string()));
+    }
+    let file_name = Path::new(db_filename)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| db_filename.to_string());
+    Ok((host.to_string(), format!("{}{}", remote_path, file_name)))
+}
+
+/// Column names of a `CREATE TABLE` statement as printed by `.schema`.
+fn schema_columns(sql: &str) -> Vec<String> {
+    let (Some(start), Some(end)) = (sql.find('('), sql.rfind(')')) else {
+        return Vec::new();
+    };
+    sql[start + 1..end]
+        .split(',')
+        .filter_map(|def| def.split_whitespace().next())
+        .map(|name| name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']')).to_string())
+        .collect()
+}
+
+/// Diff the local columns of `options.table` (`data` unless deploying with
+/// `--table-name`) against the deployed database (read with `ssh <host>
+/// sqlite3 <db> ".schema <table>"`) and refuse the upload on a change unless
+/// `allow_schema_change` is set. A remote database without the table (first
+/// deploy) passes.
+fn check_remote_schema(
+    db_filename: &str,
+    remote_dest: &str,
+    options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    let (host, remote_path) = remote_file(db_filename, remote_dest)?;
+    let table = &options.table;
+    let local: Vec<String> = {
+        let conn = Connection::open(db_filename)?;
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+        let names = stmt.query_map([table], |row| row.get(0))?;
+        names.collect::<Result<_, _>>()?
+    };
+
+    let output = Command::new("ssh")
+        .args(options.connection_args("-p"))
+        .arg(&host)
+        .arg("sqlite3")
+        .arg(shell_quote(&remote_path))
+        .arg(shell_quote(&format!(".schema {}", table)))
+        .output()
+        .map_err(|e| spawn_error("ssh", e))?;
+    if !output.status.success() {
+        return Err(Fb2SqliteError::Upload(format!(
+            "Remote schema check failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    let remote = schema_columns(&String::from_utf8_lossy(&output.stdout));
+    if remote.is_empty() {
+        println!(
+            "Schema check: no {} table in remote {}, nothing to compare",
+            table, remote_path
+        );
+        return Ok(());
+    }
+
+    let mut added: Vec<&String> = local.iter().filter(|c| !remote.contains(c)).collect();
+    let mut removed: Vec<&String> = remote.iter().filter(|c| !local.contains(c)).collect();
+    // A removed and an added column at the same position count as a rename
+    let mut renamed = Vec::new();
+    for (old, new) in remote.iter().zip(&local) {
+        if removed.contains(&old) && added.contains(&new) {
+            renamed.push(format!("{} -> {}", old, new));
+            removed.retain(|c| *c != old);
+            added.retain(|c| *c != new);
+        }
+    }
+    if added.is_empty() && removed.is_empty() && renamed.is_empty() {
+        println!("Schema check: {} matches the deployed database", table);
+        return Ok(());
+    }
+    let join = |cols: &[&String]| cols.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", ");
+    println!("Schema of {} differs from the deployed {}:", table, remote_path);
+    if !added.is_empty() {
+        println!("  added:   {}", join(&added));
+    }
+    if !removed.is_empty() {
+        println!("  removed: {}", join(&removed));
+    }
+    if !renamed.is_empty() {
+        println!("  renamed: {}", renamed.join(", "));
+    }
+    if options.allow_schema_change {
+        println!("Uploading anyway (--allow-schema-change)");
+        Ok(())
+    } else {
+        Err(Fb2SqliteError::Upload(
+            "Schema change detected, upload aborted (pass --allow-schema-change to upload)".into(),
+        )
+        .into())
+    }
+}
+
+/// Compare the SHA-256 of the local file with the uploaded copy.
+fn verify_upload(
+    db_filename: &str,
+    host: &str,
+    remote_path: &str,
+    options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    let local = sha256_hex(&fs::read(db_filename)?);
+    let output = Command::new("ssh")
+        .args(options.connection_args("-p"))
+        .arg(host)
+        .arg("sha256sum")
+        .arg(shell_quote(remote_path))
+        .output()
+        .map_err(|e| spawn_error("ssh", e))?;
+    if !output.status.success() {
+        return Err(Fb2SqliteError::Upload(format!(
+            "Remote sha256sum failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let remote = stdout.split_whitespace().next().unwrap_or_default();
+    if remote != local {
+        return Err(Fb2SqliteError::Upload(format!(
+            "Upload verification failed: local sha256 {} != remote {}",
+            local, remote
+        ))
+        .into());
+    }
+    println!("Upload verified (sha256 {}).", local);
+    Ok(())
+}
+
+/// Lowercase hex SHA-256 digest.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(db_filename: &str, remote_dest: &str) -> (String, String) {
+        remote_file(db_filename, remote_dest).unwrap()
+    }
+
+    #[test]
+    fn remote_file_resolves_directories_to_the_local_name() {
+        assert_eq!(
+            resolved("out/firstbase.db", "host:/srv/data/"),
+            ("host".to_string(), "/srv/data/firstbase.db".to_string())
+        );
+        assert_eq!(
+            resolved("firstbase.db", "user@host:/srv/data/latest.db"),
+            ("user@host".to_string(), "/srv/data/latest.db".to_string())
+        );
+    }
+
+    #[test]
+    fn remote_file_rejects_destinations_without_host_or_path() {
+        for remote_dest in ["/srv/data/", "host:", ":/srv/data/"] {
+            assert!(
+                remote_file("firstbase.db", remote_dest).is_err(),
+                "{}",
+                remote_dest
+            );
+        }
+    }
+
+    #[test]
+    fn versioned_path_stamps_before_the_extension() {
+        let stamp = "14.10.2026";
+        assert_eq!(
+            versioned_path("/srv/firstbase.db", stamp),
+            "/srv/firstbase_14.10.2026.db"
+        );
+        assert_eq!(
+            versioned_path("/srv/data.v2/firstbase", stamp),
+            "/srv/data.v2/firstbase_14.10.2026"
+        );
+        assert_eq!(
+            versioned_path("/srv/.hidden", stamp),
+            "/srv/.hidden_14.10.2026"
+        );
+    }
+
+    #[test]
+    fn control_path_fits_a_unix_socket_path() {
+        // ssh expands %C to 40 hex chars and appends a 17-char suffix while
+        // it sets up the master connection
+        let expanded = control_path().replace("%C", &"0".repeat(40));
+        assert!(expanded.len() + 17 < 104, "{}", expanded);
+    }
+}
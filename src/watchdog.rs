@@ -0,0 +1,35 @@
+use crate::error::{Fb2SqliteError, EXIT_TIMEOUT};
+use crate::interrupt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Time the run gets to roll back after the limit before the process exits.
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// The `--max-runtime` in seconds once it was exceeded, else 0.
+static TIMED_OUT: AtomicU64 = AtomicU64::new(0);
+
+/// `--max-runtime`: after `limit` seconds stop the run like a Ctrl-C (the
+/// writer threads roll back, a Parquet file is deleted), and exit with
+/// `EXIT_TIMEOUT` if it is still running after `GRACE_PERIOD`, e.g. in a
+/// stuck download or upload.
+pub fn start(limit: u64) {
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(limit));
+        TIMED_OUT.store(limit, Ordering::SeqCst);
+        eprintln!("--max-runtime of {}s exceeded, stopping the run", limit);
+        interrupt::stop();
+        thread::sleep(GRACE_PERIOD);
+        eprintln!("Error: {}", Fb2SqliteError::Timeout(limit));
+        std::process::exit(EXIT_TIMEOUT.into());
+    });
+}
+
+/// The limit, if the run was stopped by `--max-runtime`.
+pub fn timed_out() -> Option<u64> {
+    match TIMED_OUT.load(Ordering::SeqCst) {
+        0 => None,
+        limit => Some(limit),
+    }
+}